@@ -13,6 +13,9 @@ for FILE in $INPUTS/*.txt; do
     head -c 1 $FILE > ${OUT_DIR}/${BASENAME}.c1.out
     head -c 2 $FILE > ${OUT_DIR}/${BASENAME}.c2.out
     head -c 4 $FILE > ${OUT_DIR}/${BASENAME}.c4.out
+    head -n -2 $FILE > ${OUT_DIR}/${BASENAME}.n-2.out
+    head -c -3 $FILE > ${OUT_DIR}/${BASENAME}.c-3.out
+    tail -n 2 $FILE > ${OUT_DIR}/${BASENAME}.t2.out
 done
 
 ALL="$INPUTS/empty.txt $INPUTS/one.txt $INPUTS/two.txt $INPUTS/three.txt \
@@ -23,3 +26,6 @@ head -n 4 $ALL > $OUT_DIR/all.n4.out
 head -c 1 $ALL > $OUT_DIR/all.c1.out
 head -c 2 $ALL > $OUT_DIR/all.c2.out
 head -c 4 $ALL > $OUT_DIR/all.c4.out
+head -n -2 $ALL > $OUT_DIR/all.n-2.out
+head -c -3 $ALL > $OUT_DIR/all.c-3.out
+tail -n 2 $ALL > $OUT_DIR/all.t2.out
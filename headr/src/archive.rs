@@ -0,0 +1,137 @@
+//! `--archive`: print the head of every member of a tar or zip archive.
+//!
+//! Tarballs may be gzip or zstd compressed. Inputs that are not archives are
+//! passed through as a single member without a name.
+
+use crate::decompress::decompress;
+use crate::{open, HeadrError};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek};
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const EMPTY_ZIP_MAGIC: &[u8] = b"PK\x05\x06";
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+
+/// Calls `visit` with the name and contents of each regular file in `filename`,
+/// or with `None` and the whole input if it is not an archive.
+pub(crate) fn for_each_member(
+    filename: &str,
+    mut visit: impl FnMut(Option<&str>, &mut dyn BufRead) -> Result<(), HeadrError>,
+) -> Result<(), HeadrError> {
+    let read_error = |err| HeadrError::read(filename, err);
+    let mut input = open(filename, false)?;
+    let start = input.fill_buf().map_err(read_error)?;
+    if start.starts_with(ZIP_MAGIC) || start.starts_with(EMPTY_ZIP_MAGIC) {
+        // The zip directory is at the end, so the archive has to be seekable.
+        let archive: Box<dyn ReadSeek> = match filename {
+            "-" => {
+                let mut data = Vec::new();
+                input.read_to_end(&mut data).map_err(read_error)?;
+                Box::new(Cursor::new(data))
+            }
+            _ => Box::new(File::open(filename).map_err(|err| HeadrError::open(filename, err))?),
+        };
+        return zip_members(archive, &mut visit).map_err(|err| err.into_headr(filename));
+    }
+
+    let mut input = decompress(input).map_err(read_error)?;
+    let mut header = Vec::with_capacity(512);
+    input
+        .by_ref()
+        .take(512)
+        .read_to_end(&mut header)
+        .map_err(read_error)?;
+    let is_tar =
+        header.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC);
+    let mut input = BufReader::new(Cursor::new(header).chain(input));
+    if is_tar {
+        tar_members(input, &mut visit).map_err(|err| err.into_headr(filename))
+    } else {
+        visit(None, &mut input)
+    }
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Keeps errors from reading the archive apart from those of printing a member.
+enum Member {
+    Read(io::Error),
+    // Members are only visited in the formats headr was built with.
+    #[cfg_attr(not(any(feature = "tar", feature = "zip")), allow(dead_code))]
+    Visit(HeadrError),
+}
+
+impl Member {
+    fn into_headr(self, filename: &str) -> HeadrError {
+        match self {
+            Member::Read(err) => HeadrError::read(filename, err),
+            Member::Visit(err) => err,
+        }
+    }
+}
+
+impl From<io::Error> for Member {
+    fn from(err: io::Error) -> Member {
+        Member::Read(err)
+    }
+}
+
+#[cfg(feature = "zip")]
+impl From<zip::result::ZipError> for Member {
+    fn from(err: zip::result::ZipError) -> Member {
+        Member::Read(err.into())
+    }
+}
+
+#[cfg(feature = "tar")]
+fn tar_members(
+    input: impl Read,
+    visit: &mut impl FnMut(Option<&str>, &mut dyn BufRead) -> Result<(), HeadrError>,
+) -> Result<(), Member> {
+    let mut archive = tar::Archive::new(input);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.display().to_string();
+        visit(Some(&name), &mut BufReader::new(entry)).map_err(Member::Visit)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "tar"))]
+fn tar_members(
+    _input: impl Read,
+    _visit: &mut impl FnMut(Option<&str>, &mut dyn BufRead) -> Result<(), HeadrError>,
+) -> Result<(), Member> {
+    Err(Member::Read(crate::decompress::not_built_with("tar")))
+}
+
+#[cfg(feature = "zip")]
+fn zip_members(
+    archive: impl Read + Seek,
+    visit: &mut impl FnMut(Option<&str>, &mut dyn BufRead) -> Result<(), HeadrError>,
+) -> Result<(), Member> {
+    let mut archive = zip::ZipArchive::new(archive)?;
+    for index in 0..archive.len() {
+        let member = archive.by_index(index)?;
+        if !member.is_file() {
+            continue;
+        }
+        let name = member.name().to_string();
+        visit(Some(&name), &mut BufReader::new(member)).map_err(Member::Visit)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "zip"))]
+fn zip_members(
+    _archive: impl Read + Seek,
+    _visit: &mut impl FnMut(Option<&str>, &mut dyn BufRead) -> Result<(), HeadrError>,
+) -> Result<(), Member> {
+    Err(Member::Read(crate::decompress::not_built_with("zip")))
+}
@@ -0,0 +1,27 @@
+//! Keeps binary files from being written to a terminal unless asked for.
+
+/// Whether `block`, the start of a file, looks like binary data: it holds a
+/// NUL byte or is not valid UTF-8. A character cut off at the end is allowed.
+pub(crate) fn looks_binary(block: &[u8]) -> bool {
+    if block.contains(&0) {
+        return true;
+    }
+    match std::str::from_utf8(block) {
+        Ok(_) => false,
+        Err(err) => err.error_len().is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::looks_binary;
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(!looks_binary(b""));
+        assert!(!looks_binary("Öne line\r\n".as_bytes()));
+        assert!(!looks_binary(&"Ö".as_bytes()[..1]));
+        assert!(looks_binary(b"ELF\0\x02"));
+        assert!(looks_binary(b"\x89PNG\r\n"));
+    }
+}
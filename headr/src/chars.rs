@@ -0,0 +1,113 @@
+//! `--chars`: the first N characters or grapheme clusters of UTF-8 input.
+
+use clap::ValueEnum;
+use std::io;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What `Head::Chars` counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharUnit {
+    /// Unicode scalar values, i.e. `char`s.
+    Scalar,
+    /// Extended grapheme clusters, e.g. `e` followed by a combining accent.
+    Grapheme,
+}
+
+/// What `Head::Chars` does with bytes that are not valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InvalidUtf8 {
+    /// Print U+FFFD for each invalid sequence and count it as one character
+    Replace,
+    /// Drop invalid sequences without counting them
+    Skip,
+    /// Stop with a read error
+    Fail,
+}
+
+/// Appends up to `max` whole units from the start of `data` to `out`.
+///
+/// Returns how many units were taken and how many bytes of `data` they used.
+/// Unless `eof`, a character cut off at the end of `data`, or a grapheme that
+/// might go on past it, is left for the next call.
+pub(crate) fn take(
+    data: &[u8],
+    unit: CharUnit,
+    invalid: InvalidUtf8,
+    eof: bool,
+    max: u64,
+    out: &mut Vec<u8>,
+) -> io::Result<(u64, usize)> {
+    let mut taken = 0;
+    let mut pos = 0;
+    while taken < max && pos < data.len() {
+        let rest = &data[pos..];
+        let (text, bad_len) = match std::str::from_utf8(rest) {
+            Ok(text) => (text, None),
+            Err(err) => {
+                let valid = &rest[..err.valid_up_to()];
+                // Cannot fail: `valid_up_to` is the end of the valid prefix.
+                let text = std::str::from_utf8(valid).unwrap_or_default();
+                let bad_len = err.error_len().or(if eof {
+                    Some(rest.len() - valid.len())
+                } else {
+                    None
+                });
+                (text, bad_len)
+            }
+        };
+        // Whether more text may follow `text` in the next call.
+        let open_end = !eof && bad_len.is_none();
+
+        match unit {
+            CharUnit::Scalar => {
+                let mut end = 0;
+                for c in text.chars() {
+                    if taken == max {
+                        break;
+                    }
+                    end += c.len_utf8();
+                    taken += 1;
+                }
+                out.extend_from_slice(&text.as_bytes()[..end]);
+                pos += end;
+            }
+            CharUnit::Grapheme => {
+                let mut end = 0;
+                for grapheme in text.graphemes(true) {
+                    end += grapheme.len();
+                    if taken == max || (open_end && end == text.len()) {
+                        return Ok((taken, pos));
+                    }
+                    out.extend_from_slice(grapheme.as_bytes());
+                    taken += 1;
+                    pos += grapheme.len();
+                }
+            }
+        }
+        if taken == max {
+            break;
+        }
+
+        let bad_len = match bad_len {
+            Some(len) => len,
+            None => break,
+        };
+        match invalid {
+            InvalidUtf8::Replace => {
+                out.extend_from_slice(char::REPLACEMENT_CHARACTER.to_string().as_bytes());
+                taken += 1;
+            }
+            InvalidUtf8::Skip => {}
+            // Hand out what came before first; the next call starts at the bad bytes.
+            InvalidUtf8::Fail if taken > 0 => return Ok((taken, pos)),
+            InvalidUtf8::Fail => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid UTF-8 in input",
+                ))
+            }
+        }
+        pos += bad_len;
+    }
+    Ok((taken, pos))
+}
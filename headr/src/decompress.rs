@@ -0,0 +1,49 @@
+//! `-z/--decompress`: transparently read gzip and zstd compressed input.
+
+use std::io::{self, BufRead};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Wraps `reader` in a decoder if it starts like a compressed stream, and
+/// returns it unchanged otherwise.
+pub(crate) fn decompress(mut reader: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+    let start = reader.fill_buf()?;
+    if start.starts_with(GZIP_MAGIC) {
+        gzip(reader)
+    } else if start.starts_with(ZSTD_MAGIC) {
+        zstd(reader)
+    } else {
+        Ok(reader)
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn gzip(reader: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+    let decoder = flate2::bufread::MultiGzDecoder::new(reader);
+    Ok(Box::new(io::BufReader::new(decoder)))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gzip(_reader: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+    Err(not_built_with("gzip"))
+}
+
+#[cfg(feature = "zstd")]
+fn zstd(reader: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+    let decoder = zstd::Decoder::with_buffer(reader)?;
+    Ok(Box::new(io::BufReader::new(decoder)))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd(_reader: Box<dyn BufRead>) -> io::Result<Box<dyn BufRead>> {
+    Err(not_built_with("zstd"))
+}
+
+#[cfg(not(all(feature = "gzip", feature = "zstd", feature = "tar", feature = "zip")))]
+pub(crate) fn not_built_with(feature: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("headr was built without the `{}` feature", feature),
+    )
+}
@@ -0,0 +1,165 @@
+//! `--csv` and `--tsv`: count records instead of lines and always keep the header.
+//!
+//! Records are parsed with `csv_core`, which takes no more input than the
+//! record it parses, so stdin is left right after the last record printed.
+//! They are printed with the bytes they have in the input, quotes and all.
+
+use crate::{Count, HeadrError};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use unicode_width::UnicodeWidthStr;
+
+/// A record as it is in the input, and its fields.
+struct Record {
+    raw: Vec<u8>,
+    fields: Vec<Vec<u8>>,
+}
+
+/// Prints the header and the first (or all but the last) records of `file`.
+pub(crate) fn print_head(
+    file: impl BufRead,
+    filename: &str,
+    delimiter: u8,
+    count: Count,
+    table: bool,
+    out: &mut impl Write,
+) -> Result<(), HeadrError> {
+    let read_error = |err| HeadrError::read(filename, err);
+    let mut records = Records::new(file, delimiter);
+    let header = match records.next_record().map_err(read_error)? {
+        Some(header) => header,
+        None => return Ok(()),
+    };
+
+    let mut selected = vec![header];
+    match count {
+        Count::First(n) => {
+            for _ in 0..n {
+                match records.next_record().map_err(read_error)? {
+                    Some(record) => selected.push(record),
+                    None => break,
+                }
+            }
+        }
+        Count::AllButLast(n) => {
+            let mut held = VecDeque::new();
+            while let Some(record) = records.next_record().map_err(read_error)? {
+                held.push_back(record);
+                if held.len() as u64 > n {
+                    selected.extend(held.pop_front());
+                }
+            }
+        }
+    }
+
+    if table {
+        print_table(out, &selected)
+    } else {
+        selected
+            .iter()
+            .try_for_each(|record| out.write_all(&record.raw))
+            .and_then(|()| out.flush())
+    }
+    .map_err(HeadrError::Write)
+}
+
+/// The records of a reader, read one at a time.
+struct Records<R> {
+    file: R,
+    reader: csv_core::Reader,
+    done: bool,
+}
+
+impl<R: BufRead> Records<R> {
+    fn new(file: R, delimiter: u8) -> Self {
+        Records {
+            file,
+            reader: csv_core::ReaderBuilder::new().delimiter(delimiter).build(),
+            done: false,
+        }
+    }
+
+    /// The next record, with the blank lines before it, or `None` at the end.
+    fn next_record(&mut self) -> io::Result<Option<Record>> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut raw = Vec::new();
+        let mut data = Vec::new();
+        let mut bounds = Vec::new();
+        let mut output = [0; 4096];
+        let mut ends = [0; 64];
+        loop {
+            let input = self.file.fill_buf()?;
+            let (result, nin, nout, nends) = self.reader.read_record(input, &mut output, &mut ends);
+            raw.extend_from_slice(&input[..nin]);
+            self.file.consume(nin);
+            data.extend_from_slice(&output[..nout]);
+            // Ends count from the start of the record, not of `output`.
+            bounds.extend_from_slice(&ends[..nends]);
+            match result {
+                csv_core::ReadRecordResult::Record => break,
+                csv_core::ReadRecordResult::End => {
+                    self.done = true;
+                    return Ok(None);
+                }
+                _ => {}
+            }
+        }
+        // The parser stops at the `\r` of a `\r\n`, which belongs to this record.
+        if raw.ends_with(b"\r") && self.file.fill_buf()?.first() == Some(&b'\n') {
+            raw.push(b'\n');
+            self.file.consume(1);
+        }
+        let mut start = 0;
+        let fields = bounds
+            .iter()
+            .map(|&end| {
+                let field = data[start..end].to_vec();
+                start = end;
+                field
+            })
+            .collect();
+        Ok(Some(Record { raw, fields }))
+    }
+}
+
+/// Prints `records` in left-aligned columns, with a rule below the header.
+fn print_table(mut out: impl Write, records: &[Record]) -> io::Result<()> {
+    let rows: Vec<Vec<String>> = records
+        .iter()
+        .map(|record| record.fields.iter().map(|field| cell(field)).collect())
+        .collect();
+    let mut widths = Vec::new();
+    for row in &rows {
+        for (column, cell) in row.iter().enumerate() {
+            let width = cell.width();
+            match widths.get_mut(column) {
+                Some(max) if *max < width => *max = width,
+                Some(_) => {}
+                None => widths.push(width),
+            }
+        }
+    }
+    for (row_num, row) in rows.iter().enumerate() {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - cell.width())))
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end())?;
+        if row_num == 0 {
+            let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+            writeln!(out, "{}", rule.join("  "))?;
+        }
+    }
+    out.flush()
+}
+
+/// A field as it is shown in a table cell, with line breaks and tabs made visible.
+fn cell(field: &[u8]) -> String {
+    String::from_utf8_lossy(field)
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
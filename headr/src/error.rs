@@ -0,0 +1,56 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while printing the head of a file.
+#[derive(Debug)]
+pub enum HeadrError {
+    /// The file could not be opened.
+    Open { filename: String, source: io::Error },
+    /// The file was opened, but reading from it failed.
+    Read { filename: String, source: io::Error },
+    /// A command-line value was rejected.
+    InvalidArgument(String),
+    /// Standard output could not be written to.
+    Write(io::Error),
+}
+
+impl HeadrError {
+    pub(crate) fn open(filename: &str, source: io::Error) -> HeadrError {
+        HeadrError::Open {
+            filename: filename.to_string(),
+            source,
+        }
+    }
+
+    pub(crate) fn read(filename: &str, source: io::Error) -> HeadrError {
+        HeadrError::Read {
+            filename: filename.to_string(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for HeadrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeadrError::Open { filename, source } => write!(f, "{}: {}", filename, source),
+            HeadrError::Read { filename, source } => {
+                write!(f, "error reading {}: {}", filename, source)
+            }
+            HeadrError::InvalidArgument(msg) => write!(f, "{}", msg),
+            HeadrError::Write(source) => write!(f, "error writing to stdout: {}", source),
+        }
+    }
+}
+
+impl Error for HeadrError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HeadrError::Open { source, .. }
+            | HeadrError::Read { source, .. }
+            | HeadrError::Write(source) => Some(source),
+            HeadrError::InvalidArgument(_) => None,
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! `--follow` support: keep printing data appended to the files given to `--tail`.
+//!
+//! Files are re-checked whenever inotify reports activity in their directory,
+//! or every `--sleep-interval` seconds when inotify is not available.
+
+use crate::{print_banner, print_tail, Config, FollowMode, HeadrError, ProgResult};
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+/// A file being followed, together with how far it has been printed.
+struct Followed {
+    name: String,
+    file: Option<File>,
+    pos: u64,
+    id: Option<(u64, u64)>,
+    given_up: bool,
+}
+
+/// Prints banners when the output switches from one file to another.
+struct Output {
+    show_banners: bool,
+    last: Option<usize>,
+}
+
+impl Output {
+    fn write(&mut self, index: usize, name: &str, data: &[u8]) -> Result<(), HeadrError> {
+        if self.show_banners && self.last != Some(index) {
+            print_banner(name, self.last.is_none());
+        }
+        self.last = Some(index);
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(data).map_err(HeadrError::Write)?;
+        stdout.flush().map_err(HeadrError::Write)
+    }
+}
+
+pub(crate) fn run(config: &Config, mode: FollowMode, num_lines: usize) -> ProgResult<ExitCode> {
+    let retry = config.retry();
+    let mut exit_code = ExitCode::SUCCESS;
+    let mut output = Output {
+        show_banners: config.files.len() > 1,
+        last: None,
+    };
+    let mut followed = Vec::new();
+    for (file_num, filename) in config.files.iter().enumerate() {
+        if filename == "-" {
+            // Standard input is printed, but there is nothing to re-check.
+            if output.show_banners {
+                print_banner(filename, file_num == 0);
+            }
+            print_tail(crate::stdin::open(), filename, num_lines, &mut io::stdout())?;
+            output.last = Some(file_num);
+            continue;
+        }
+        let mut entry = Followed {
+            name: filename.clone(),
+            file: None,
+            pos: 0,
+            id: None,
+            given_up: false,
+        };
+        match File::open(filename) {
+            Err(err) => {
+                eprintln!("{}", HeadrError::open(filename, err));
+                exit_code = ExitCode::FAILURE;
+                if !retry {
+                    continue;
+                }
+            }
+            Ok(file) => {
+                if output.show_banners {
+                    print_banner(filename, file_num == 0);
+                }
+                output.last = Some(file_num);
+                entry.pos = print_tail(
+                    BufReader::new(&file),
+                    filename,
+                    num_lines,
+                    &mut io::stdout(),
+                )?;
+                entry.id = file.metadata().ok().and_then(|m| file_id(&m));
+                entry.file = Some(file);
+            }
+        }
+        followed.push((file_num, entry));
+    }
+
+    let interval = config.sleep_interval;
+    let mut waiter = Waiter::new(followed.iter().map(|(_, f)| f.name.as_str()));
+    loop {
+        // Check the process before reading, so data written just before it
+        // exited is still printed.
+        let pid_exited = config.pid.is_some_and(|pid| !process_alive(pid));
+        for (index, entry) in followed.iter_mut() {
+            if mode == FollowMode::Name || entry.file.is_none() {
+                check_name(entry, retry, &mut output, *index)?;
+            }
+            read_appended(entry, &mut output, *index)?;
+        }
+        if pid_exited {
+            break;
+        }
+        if !retry && followed.iter().all(|(_, f)| f.file.is_none()) {
+            eprintln!("headr: no files remaining");
+            break;
+        }
+        waiter.wait(interval);
+    }
+    Ok(exit_code)
+}
+
+/// Re-opens `entry` when the file behind its name was replaced, e.g. by log rotation.
+fn check_name(
+    entry: &mut Followed,
+    retry: bool,
+    output: &mut Output,
+    index: usize,
+) -> Result<(), HeadrError> {
+    let current_id = fs::metadata(&entry.name).ok().and_then(|m| file_id(&m));
+    if entry.given_up || (entry.file.is_some() && current_id.is_some() && current_id == entry.id) {
+        return Ok(());
+    }
+    // Whatever was written to the old file before it went away is still shown.
+    read_appended(entry, output, index)?;
+    let had_file = entry.file.is_some();
+    match current_id {
+        None => {
+            if had_file {
+                eprintln!("headr: '{}' has become inaccessible", entry.name);
+                entry.file = None;
+                entry.id = None;
+                entry.given_up = !retry;
+            }
+        }
+        Some(_) => {
+            if let Ok(file) = File::open(&entry.name) {
+                eprintln!(
+                    "headr: '{}' has {};  following new file",
+                    entry.name,
+                    if had_file {
+                        "been replaced"
+                    } else {
+                        "appeared"
+                    }
+                );
+                entry.id = file.metadata().ok().and_then(|m| file_id(&m));
+                entry.file = Some(file);
+                entry.pos = 0;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints everything written past `entry.pos`, starting over if the file was truncated.
+fn read_appended(
+    entry: &mut Followed,
+    output: &mut Output,
+    index: usize,
+) -> Result<(), HeadrError> {
+    let mut file = match &entry.file {
+        Some(file) => file,
+        None => return Ok(()),
+    };
+    let read_error = |err| HeadrError::read(&entry.name, err);
+    let metadata = file.metadata().map_err(read_error)?;
+    if metadata.is_file() && metadata.len() < entry.pos {
+        eprintln!("headr: {}: file truncated", entry.name);
+        entry.pos = file.seek(SeekFrom::Start(0)).map_err(read_error)?;
+    }
+    let mut buffer = [0; 8192];
+    loop {
+        let bytes_read = file.read(&mut buffer).map_err(read_error)?;
+        if bytes_read == 0 {
+            break;
+        }
+        entry.pos += bytes_read as u64;
+        output.write(index, &entry.name, &buffer[..bytes_read])?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    // Without inode numbers, a file is considered replaced only when it disappears.
+    let _ = metadata;
+    Some((0, 0))
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    // Signal 0 only checks whether the process exists and may be signalled.
+    let ret = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    ret == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Blocks until a followed file may have changed.
+enum Waiter {
+    #[cfg(target_os = "linux")]
+    Inotify(inotify::Inotify),
+    Poll,
+}
+
+impl Waiter {
+    #[cfg(target_os = "linux")]
+    fn new<'a>(names: impl Iterator<Item = &'a str>) -> Waiter {
+        use inotify::{Inotify, WatchMask};
+
+        let inotify = match Inotify::init() {
+            Ok(inotify) => inotify,
+            Err(_) => return Waiter::Poll,
+        };
+        for name in names {
+            // Watching the directory also catches files created by rotation.
+            let dir = match Path::new(name).parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            let dir_mask = WatchMask::MODIFY
+                | WatchMask::ATTRIB
+                | WatchMask::CREATE
+                | WatchMask::DELETE
+                | WatchMask::MOVED_FROM
+                | WatchMask::MOVED_TO;
+            let file_mask = WatchMask::MODIFY
+                | WatchMask::ATTRIB
+                | WatchMask::DELETE_SELF
+                | WatchMask::MOVE_SELF;
+            if inotify.watches().add(dir, dir_mask).is_err() {
+                return Waiter::Poll;
+            }
+            // The file itself may be missing until --retry finds it.
+            let _ = inotify.watches().add(name, file_mask);
+        }
+        Waiter::Inotify(inotify)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn new<'a>(_names: impl Iterator<Item = &'a str>) -> Waiter {
+        Waiter::Poll
+    }
+
+    fn wait(&mut self, interval: Duration) {
+        match self {
+            #[cfg(target_os = "linux")]
+            Waiter::Inotify(inotify) => {
+                use std::os::unix::io::AsRawFd;
+
+                let mut fds = libc::pollfd {
+                    fd: inotify.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                let timeout = interval.as_millis().min(i32::MAX as u128) as i32;
+                unsafe { libc::poll(&mut fds, 1, timeout) };
+                // Only the wake-up matters, the files are all checked anyway.
+                let mut buffer = [0; 4096];
+                while let Ok(events) = inotify.read_events(&mut buffer) {
+                    if events.count() == 0 {
+                        break;
+                    }
+                }
+            }
+            Waiter::Poll => thread::sleep(interval),
+        }
+    }
+}
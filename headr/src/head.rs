@@ -0,0 +1,415 @@
+//! The head of a reader, independent of the command line.
+//!
+//! ```
+//! use headr::{Count, Head};
+//!
+//! let text = "one\ntwo\nthree\n";
+//! let mut out = Vec::new();
+//! headr::write_head(text.as_bytes(), Head::Lines(Count::AllButLast(1)), &mut out).unwrap();
+//! assert_eq!(out, b"one\ntwo\n");
+//! ```
+
+use crate::chars::{self, CharUnit, InvalidUtf8};
+use crate::HeadrError;
+use regex::bytes::Regex;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+/// How many lines or bytes to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Count {
+    /// The first N, like `-n 5`.
+    First(u64),
+    /// Everything except the last N, like `-n -5`.
+    AllButLast(u64),
+}
+
+/// Which part of the input is the head.
+#[derive(Debug, Clone)]
+pub enum Head {
+    Lines(Count),
+    Bytes(Count),
+    /// Records that end in the given separator instead of a newline.
+    Records(Count, Vec<u8>),
+    /// Lines up to and including the first one matching the pattern.
+    Until(Regex),
+    /// The first N characters or graphemes; a character is never cut in half.
+    Chars(u64, CharUnit, InvalidUtf8),
+}
+
+impl FromStr for Count {
+    type Err = HeadrError;
+
+    /// Parses GNU head's `[-]NUM[SUFFIX]`, e.g. `10`, `-3` or `2K`.
+    fn from_str(val: &str) -> Result<Count, HeadrError> {
+        let invalid = || HeadrError::InvalidArgument(format!("invalid count -- {}", val));
+        let (all_but_last, num) = match val.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, val),
+        };
+        let digits = num.find(|c: char| !c.is_ascii_digit()).unwrap_or(num.len());
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let multiplier = multiplier(&num[digits..]).ok_or_else(invalid)?;
+        let n = num[..digits]
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(multiplier))
+            .ok_or_else(invalid)?;
+        Ok(if all_but_last {
+            Count::AllButLast(n)
+        } else {
+            Count::First(n)
+        })
+    }
+}
+
+/// The multipliers GNU head accepts after a count.
+fn multiplier(suffix: &str) -> Option<u64> {
+    let (base, power): (u64, u32) = match suffix {
+        "" => return Some(1),
+        "b" => return Some(512),
+        "kB" => (1000, 1),
+        "K" | "KiB" => (1024, 1),
+        "MB" => (1000, 2),
+        "M" | "MiB" => (1024, 2),
+        "GB" => (1000, 3),
+        "G" | "GiB" => (1024, 3),
+        "TB" => (1000, 4),
+        "T" | "TiB" => (1024, 4),
+        "PB" => (1000, 5),
+        "P" | "PiB" => (1024, 5),
+        "EB" => (1000, 6),
+        "E" | "EiB" => (1024, 6),
+        _ => return None,
+    };
+    base.checked_pow(power)
+}
+
+/// Iterator over the head of a reader, in the chunks it was read in.
+///
+/// Lines and records are yielded one at a time, bytes in pieces of at most
+/// the reader's buffer size. Nothing past the head is consumed from the reader, except
+/// what has to be looked at to find out where "all but the last N" ends.
+pub struct Chunks<R> {
+    reader: R,
+    head: Head,
+    /// Lines, records, bytes or characters still to be yielded for `Count::First`
+    /// and `Head::Chars`.
+    remaining: u64,
+    /// Lines or records held back for `Count::AllButLast`.
+    held_lines: VecDeque<Vec<u8>>,
+    /// Bytes held back for `Bytes(Count::AllButLast)`, or the start of a
+    /// character or grapheme that continues in the next buffer for `Head::Chars`.
+    held_bytes: Vec<u8>,
+    done: bool,
+}
+
+/// Returns the head of `reader` as an iterator of byte chunks.
+pub fn chunks<R: BufRead>(reader: R, head: Head) -> Chunks<R> {
+    let remaining = match head {
+        Head::Lines(Count::First(n))
+        | Head::Bytes(Count::First(n))
+        | Head::Records(Count::First(n), _)
+        | Head::Chars(n, _, _) => n,
+        _ => 0,
+    };
+    Chunks {
+        reader,
+        head,
+        remaining,
+        held_lines: VecDeque::new(),
+        held_bytes: Vec::new(),
+        done: false,
+    }
+}
+
+/// Writes the head of `reader` to `writer` and returns the number of bytes written.
+pub fn write_head<R: BufRead, W: Write>(reader: R, head: Head, mut writer: W) -> io::Result<u64> {
+    let mut written = 0;
+    for chunk in chunks(reader, head) {
+        let chunk = chunk?;
+        writer.write_all(&chunk)?;
+        written += chunk.len() as u64;
+    }
+    Ok(written)
+}
+
+/// Reads up to and including the next `separator`, or to the end of `reader`.
+fn read_record(reader: &mut impl BufRead, separator: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let mut record = Vec::new();
+    if let Some(&last) = separator.last() {
+        while reader.read_until(last, &mut record)? > 0 && !record.ends_with(separator) {}
+    }
+    Ok(Some(record).filter(|record| !record.is_empty()))
+}
+
+/// A line without its line ending, for matching against `^` and `$`.
+fn line_content(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+impl<R: BufRead> Chunks<R> {
+    fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let (count, separator): (Count, &[u8]) = match &self.head {
+            Head::Lines(count) => (*count, b"\n"),
+            Head::Records(count, separator) => (*count, separator),
+            Head::Bytes(count) => return self.next_bytes(*count),
+            Head::Chars(_, unit, invalid) => return self.next_chars(*unit, *invalid),
+            Head::Until(pattern) => {
+                let line = read_record(&mut self.reader, b"\n")?;
+                match &line {
+                    Some(line) if !pattern.is_match(line_content(line)) => {}
+                    _ => self.done = true,
+                }
+                return Ok(line);
+            }
+        };
+        match count {
+            Count::First(_) => {
+                if self.remaining == 0 {
+                    return Ok(None);
+                }
+                self.remaining -= 1;
+                read_record(&mut self.reader, separator)
+            }
+            Count::AllButLast(n) => loop {
+                match read_record(&mut self.reader, separator)? {
+                    None => return Ok(None),
+                    Some(record) => {
+                        self.held_lines.push_back(record);
+                        if self.held_lines.len() as u64 > n {
+                            return Ok(self.held_lines.pop_front());
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    fn next_bytes(&mut self, count: Count) -> io::Result<Option<Vec<u8>>> {
+        match count {
+            Count::First(_) => {
+                let buffer = self.reader.fill_buf()?;
+                let len = buffer.len().min(self.remaining as usize);
+                if len == 0 {
+                    return Ok(None);
+                }
+                let chunk = buffer[..len].to_vec();
+                self.reader.consume(len);
+                self.remaining -= len as u64;
+                Ok(Some(chunk))
+            }
+            Count::AllButLast(n) => loop {
+                let buffer = self.reader.fill_buf()?;
+                if buffer.is_empty() {
+                    return Ok(None);
+                }
+                let len = buffer.len();
+                self.held_bytes.extend_from_slice(buffer);
+                self.reader.consume(len);
+                if self.held_bytes.len() as u64 > n {
+                    let keep = self
+                        .held_bytes
+                        .split_off(self.held_bytes.len() - n as usize);
+                    return Ok(Some(std::mem::replace(&mut self.held_bytes, keep)));
+                }
+            },
+        }
+    }
+
+    fn next_chars(&mut self, unit: CharUnit, invalid: InvalidUtf8) -> io::Result<Option<Vec<u8>>> {
+        while self.remaining > 0 {
+            let buffer = self.reader.fill_buf()?;
+            let eof = buffer.is_empty();
+            let held = self.held_bytes.len();
+            let mut data = std::mem::take(&mut self.held_bytes);
+            data.extend_from_slice(buffer);
+
+            let mut chunk = Vec::new();
+            let (taken, used) = chars::take(&data, unit, invalid, eof, self.remaining, &mut chunk)?;
+            self.remaining -= taken;
+            if self.remaining == 0 {
+                // Leave everything after the last character in the reader.
+                self.reader.consume(used.saturating_sub(held));
+            } else {
+                self.reader.consume(data.len() - held);
+                self.held_bytes = data.split_off(used);
+            }
+            if !chunk.is_empty() {
+                return Ok(Some(chunk));
+            }
+            if eof {
+                break;
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: BufRead> Iterator for Chunks<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let chunk = self.next_chunk().transpose();
+        if !matches!(chunk, Some(Ok(_))) {
+            self.done = true;
+        }
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunks, write_head, Count, Head};
+    use crate::{CharUnit, InvalidUtf8};
+    use regex::bytes::Regex;
+    use std::io::{BufReader, Cursor};
+
+    fn head(text: &str, head: Head) -> String {
+        let mut out = Vec::new();
+        write_head(Cursor::new(text), head, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_parse_count() {
+        assert_eq!("3".parse::<Count>().unwrap(), Count::First(3));
+        assert_eq!("0".parse::<Count>().unwrap(), Count::First(0));
+        assert_eq!("-3".parse::<Count>().unwrap(), Count::AllButLast(3));
+        assert_eq!("2K".parse::<Count>().unwrap(), Count::First(2048));
+        assert_eq!("-1kB".parse::<Count>().unwrap(), Count::AllButLast(1000));
+        assert_eq!("3b".parse::<Count>().unwrap(), Count::First(1536));
+        assert_eq!("1MiB".parse::<Count>().unwrap(), Count::First(1 << 20));
+
+        for bad in ["", "-", "foo", "3X", "--3", "+3", "K", "99999999999E"] {
+            let res = bad.parse::<Count>();
+            assert!(res.is_err(), "{:?} was accepted", bad);
+            assert_eq!(
+                res.unwrap_err().to_string(),
+                format!("invalid count -- {}", bad)
+            );
+        }
+    }
+
+    #[test]
+    fn test_lines() {
+        let text = "one\ntwo\nthree";
+        assert_eq!(head(text, Head::Lines(Count::First(0))), "");
+        assert_eq!(head(text, Head::Lines(Count::First(2))), "one\ntwo\n");
+        assert_eq!(head(text, Head::Lines(Count::First(5))), text);
+        assert_eq!(head(text, Head::Lines(Count::AllButLast(0))), text);
+        assert_eq!(head(text, Head::Lines(Count::AllButLast(1))), "one\ntwo\n");
+        assert_eq!(head(text, Head::Lines(Count::AllButLast(3))), "");
+    }
+
+    #[test]
+    fn test_bytes() {
+        let text = "one\ntwo\nthree";
+        assert_eq!(head(text, Head::Bytes(Count::First(5))), "one\nt");
+        assert_eq!(head(text, Head::Bytes(Count::First(50))), text);
+        assert_eq!(head(text, Head::Bytes(Count::AllButLast(0))), text);
+        assert_eq!(head(text, Head::Bytes(Count::AllButLast(6))), "one\ntwo");
+        assert_eq!(head(text, Head::Bytes(Count::AllButLast(50))), "");
+    }
+
+    #[test]
+    fn test_records() {
+        let text = "a\n--\nb\nc\n--\nd";
+        let separator = b"--\n".to_vec();
+        let records = |count| head(text, Head::Records(count, separator.clone()));
+        assert_eq!(records(Count::First(1)), "a\n--\n");
+        assert_eq!(records(Count::First(2)), "a\n--\nb\nc\n--\n");
+        assert_eq!(records(Count::First(3)), text);
+        assert_eq!(records(Count::AllButLast(1)), "a\n--\nb\nc\n--\n");
+    }
+
+    #[test]
+    fn test_until() {
+        let text = "one\ntwo\r\n\nthree\n";
+        let until = |pattern| head(text, Head::Until(Regex::new(pattern).unwrap()));
+        assert_eq!(until("w"), "one\ntwo\r\n");
+        assert_eq!(until("^two$"), "one\ntwo\r\n");
+        assert_eq!(until("^$"), "one\ntwo\r\n\n");
+        assert_eq!(until("four"), text);
+    }
+
+    #[test]
+    fn test_chars() {
+        let text = "ne\u{301}e\u{301} \u{1F468}\u{200D}\u{1F469}!";
+        let chars = |n, unit| head(text, Head::Chars(n, unit, InvalidUtf8::Replace));
+        assert_eq!(chars(0, CharUnit::Scalar), "");
+        assert_eq!(chars(2, CharUnit::Scalar), "ne");
+        assert_eq!(chars(3, CharUnit::Scalar), "ne\u{301}");
+        assert_eq!(chars(2, CharUnit::Grapheme), "ne\u{301}");
+        assert_eq!(
+            chars(5, CharUnit::Grapheme),
+            "ne\u{301}e\u{301} \u{1F468}\u{200D}\u{1F469}"
+        );
+        assert_eq!(chars(50, CharUnit::Grapheme), text);
+    }
+
+    #[test]
+    fn test_chars_invalid_utf8() {
+        let text: &[u8] = b"a\xffb\xe2\x82";
+        let chars = |n, invalid| {
+            let mut out = Vec::new();
+            write_head(text, Head::Chars(n, CharUnit::Scalar, invalid), &mut out)
+                .map(|_| String::from_utf8(out).unwrap())
+                .map_err(|err| err.to_string())
+        };
+        assert_eq!(chars(2, InvalidUtf8::Replace).unwrap(), "a\u{FFFD}");
+        assert_eq!(
+            chars(9, InvalidUtf8::Replace).unwrap(),
+            "a\u{FFFD}b\u{FFFD}"
+        );
+        assert_eq!(chars(2, InvalidUtf8::Skip).unwrap(), "ab");
+        assert_eq!(chars(9, InvalidUtf8::Skip).unwrap(), "ab");
+        assert_eq!(chars(1, InvalidUtf8::Fail).unwrap(), "a");
+        assert_eq!(
+            chars(2, InvalidUtf8::Fail).unwrap_err(),
+            "invalid UTF-8 in input"
+        );
+    }
+
+    #[test]
+    fn test_chars_across_buffers() {
+        let text = "e\u{301}\u{1F468}\u{200D}\u{1F469}\u{20AC}".repeat(5);
+        for unit in [CharUnit::Scalar, CharUnit::Grapheme] {
+            let whole = head(&text, Head::Chars(7, unit, InvalidUtf8::Fail));
+            let reader = BufReader::with_capacity(1, Cursor::new(text.as_bytes()));
+            let mut out = Vec::new();
+            write_head(reader, Head::Chars(7, unit, InvalidUtf8::Fail), &mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), whole);
+        }
+    }
+
+    #[test]
+    fn test_chunks_across_buffers() {
+        let text = "0123456789".repeat(10);
+        let reader = BufReader::with_capacity(7, Cursor::new(text.as_bytes()));
+        let pieces: Vec<_> = chunks(reader, Head::Bytes(Count::AllButLast(15)))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(pieces.len() > 1);
+        assert_eq!(pieces.concat(), &text.as_bytes()[..85]);
+    }
+
+    #[test]
+    fn test_leaves_rest_unread() {
+        let mut reader = Cursor::new("one\ntwo\nthree\n");
+        assert_eq!(chunks(&mut reader, Head::Lines(Count::First(1))).count(), 1);
+        assert_eq!(reader.position(), 4);
+        assert_eq!(chunks(&mut reader, Head::Bytes(Count::First(2))).count(), 1);
+        assert_eq!(reader.position(), 6);
+        let head = Head::Chars(2, CharUnit::Grapheme, InvalidUtf8::Fail);
+        assert_eq!(chunks(&mut reader, head).count(), 1);
+        assert_eq!(reader.position(), 8);
+    }
+}
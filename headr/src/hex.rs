@@ -0,0 +1,94 @@
+//! `--hex`: show the head as a canonical `offset  hex  |ascii|` dump.
+
+use std::io::{self, Write};
+
+/// How the bytes of a dump are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Layout {
+    /// Bytes printed together without a space in between.
+    pub(crate) group: usize,
+    /// Bytes on one line.
+    pub(crate) columns: usize,
+}
+
+/// A writer that turns the bytes written to it into a hex dump on `out`.
+///
+/// `finish` has to be called to print the last, partial line.
+pub(crate) struct HexDump<'a, W: Write> {
+    out: &'a mut W,
+    layout: Layout,
+    /// Offset of the first byte of the dump.
+    start: u64,
+    /// Offset of the first byte in `line`.
+    offset: u64,
+    line: Vec<u8>,
+}
+
+impl<'a, W: Write> HexDump<'a, W> {
+    /// Starts a dump whose offsets count from `offset`.
+    pub(crate) fn new(out: &'a mut W, layout: Layout, offset: u64) -> Self {
+        HexDump {
+            out,
+            layout,
+            start: offset,
+            offset,
+            line: Vec::with_capacity(layout.columns),
+        }
+    }
+
+    /// Prints what is left and the offset after the last byte, unless there
+    /// were no bytes at all.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        if !self.line.is_empty() {
+            self.write_line()?;
+        }
+        if self.offset > self.start {
+            writeln!(self.out, "{:08x}", self.offset)?;
+        }
+        self.out.flush()
+    }
+
+    fn write_line(&mut self) -> io::Result<()> {
+        let Layout { group, columns } = self.layout;
+        let width = 2 * columns + columns.div_ceil(group) - 1;
+        let hex: Vec<String> = self
+            .line
+            .chunks(group)
+            .map(|bytes| bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+            .collect();
+        let ascii: String = self
+            .line
+            .iter()
+            .map(|&byte| match byte {
+                b' '..=b'~' => byte as char,
+                _ => '.',
+            })
+            .collect();
+        writeln!(
+            self.out,
+            "{:08x}  {:<width$}  |{}|",
+            self.offset,
+            hex.join(" "),
+            ascii,
+            width = width
+        )?;
+        self.offset += self.line.len() as u64;
+        self.line.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for HexDump<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.layout.columns - self.line.len());
+        self.line.extend_from_slice(&buf[..len]);
+        if self.line.len() == self.layout.columns {
+            self.write_line()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -1,40 +1,394 @@
-use clap::Parser;
+use clap::{ArgGroup, Parser, ValueEnum};
+use regex::bytes::Regex;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read};
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::process::ExitCode;
 use std::string::String;
+use std::time::Duration;
+
+use hex::{HexDump, Layout};
+use skip::Skip;
+
+mod archive;
+mod binary;
+mod chars;
+mod decompress;
+mod delimited;
+mod error;
+mod follow;
+mod head;
+mod hex;
+mod line_index;
+mod parallel;
+mod sandwich;
+mod skip;
+mod stdin;
+
+pub use chars::{CharUnit, InvalidUtf8};
+pub use error::HeadrError;
+pub use head::{chunks, write_head, Chunks, Count, Head};
 
 type ProgResult<T> = Result<T, Box<dyn Error>>;
 
+// Spelled as an alias so clap takes it as one value rather than a list of bytes.
+type Separator = Vec<u8>;
+
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
+#[clap(group(ArgGroup::new("following").args(["follow", "follow_name"]).multiple(true)))]
 pub struct Config {
     #[clap(default_value = "-")]
     files: Vec<String>,
-    /// Number of lines to print
-    #[clap(short = 'n', long, default_value_t = 10, value_parser=parse_line_count)]
-    lines: usize,
-    /// Number of bytes to print
-    #[clap(short = 'c', long, conflicts_with = "lines", value_parser=parse_byte_count)]
-    bytes: Option<usize>,
+    /// Number of lines to print; with a leading '-', all but the last LINES lines
+    #[clap(
+        short = 'n',
+        long,
+        default_value = "10",
+        allow_hyphen_values = true,
+        value_parser = parse_line_count
+    )]
+    lines: Count,
+    /// Number of bytes to print; with a leading '-', all but the last BYTES bytes
+    #[clap(
+        short = 'c',
+        long,
+        conflicts_with = "lines",
+        allow_hyphen_values = true,
+        value_parser = parse_byte_count
+    )]
+    bytes: Option<Count>,
+    /// Print the last N lines instead of the first
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["lines", "bytes"],
+        value_parser = parse_tail_count
+    )]
+    tail: Option<usize>,
+    /// With --tail, also print the first N lines and how many were left out in between
+    #[clap(
+        long = "head",
+        value_name = "N",
+        requires = "tail",
+        conflicts_with_all = ["follow", "follow_name"],
+        value_parser = parse_tail_count
+    )]
+    head_lines: Option<usize>,
+    /// Start after the first N lines
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["tail", "delimited", "follow", "follow_name"],
+        value_parser = parse_skip_count
+    )]
+    skip_lines: Option<u64>,
+    /// Start after the first N bytes
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["skip_lines", "tail", "delimited", "follow", "follow_name"],
+        value_parser = parse_skip_count
+    )]
+    skip_bytes: Option<u64>,
+    /// With --skip-lines, find the lines through an index kept in FILE.headr-index
+    #[clap(long, requires = "skip_lines")]
+    line_index: bool,
+    /// Print lines up to and including the first line matching REGEX
+    #[clap(
+        long,
+        value_name = "REGEX",
+        conflicts_with_all = ["lines", "bytes", "tail", "records"],
+        value_parser = parse_regex
+    )]
+    until: Option<Regex>,
+    /// Count records ending in SEP instead of lines, e.g. '---\n' or '\n\n'
+    #[clap(
+        long,
+        value_name = "SEP",
+        conflicts_with_all = ["bytes", "tail"],
+        allow_hyphen_values = true,
+        value_parser = parse_separator
+    )]
+    records: Option<Separator>,
+    /// Print the first N characters; a character is never cut in half
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["lines", "bytes", "tail", "until", "records"],
+        value_parser = parse_char_count
+    )]
+    chars: Option<u64>,
+    /// With --chars, count grapheme clusters instead of Unicode scalar values
+    #[clap(long, requires = "chars")]
+    graphemes: bool,
+    /// With --chars, what to do with input that is not valid UTF-8
+    #[clap(
+        long,
+        value_enum,
+        value_name = "POLICY",
+        default_value = "replace",
+        requires = "chars"
+    )]
+    invalid_utf8: InvalidUtf8,
+    /// Read CSV: count records instead of lines and always print the header
+    #[clap(long, group = "delimited", conflicts_with_all = ["bytes", "tail", "until", "records", "chars"])]
+    csv: bool,
+    /// Like --csv, but with tab-separated fields
+    #[clap(long, group = "delimited", conflicts_with_all = ["bytes", "tail", "until", "records", "chars"])]
+    tsv: bool,
+    /// With --csv or --tsv, print the records as an aligned table
+    #[clap(long, requires = "delimited")]
+    table: bool,
+    /// Print the head as a hex dump with offsets and printable characters
+    #[clap(long, conflicts_with_all = ["tail", "delimited"])]
+    hex: bool,
+    /// With --hex, print N bytes together without a space in between
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        requires = "hex",
+        value_parser = parse_hex_size
+    )]
+    hex_group: usize,
+    /// With --hex, print N bytes on each line
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = 16,
+        requires = "hex",
+        value_parser = parse_hex_size
+    )]
+    hex_columns: usize,
+    /// Print binary files to a terminal instead of a notice
+    #[clap(long)]
+    force: bool,
+    /// Whether stdout is a terminal, where binary files are not printed
+    #[clap(skip)]
+    stdout_is_terminal: bool,
+    /// Read up to N files at the same time; output stays in argument order,
+    /// with at most 256 KiB of output held for each of up to 2*N files
+    #[clap(
+        short = 'j',
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        conflicts_with_all = ["archive", "follow", "follow_name"],
+        value_parser = parse_jobs
+    )]
+    jobs: usize,
+    /// Decompress gzip and zstd input
+    #[clap(short = 'z', long)]
+    decompress: bool,
+    /// Print the head of every member of tar and zip archives
+    #[clap(long, conflicts_with = "tail")]
+    archive: bool,
+    /// Output appended data as the file grows (requires --tail)
+    #[clap(
+        short = 'f',
+        long,
+        value_enum,
+        value_name = "HOW",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "descriptor",
+        requires = "tail"
+    )]
+    follow: Option<FollowMode>,
+    /// Same as --follow=name --retry
+    #[clap(short = 'F', requires = "tail")]
+    follow_name: bool,
+    /// With --follow, keep trying to open a file if it is inaccessible
+    #[clap(long, requires = "following")]
+    retry: bool,
+    /// With --follow, terminate after process ID PID dies
+    #[clap(long, value_name = "PID", requires = "following")]
+    pid: Option<u32>,
+    /// With --follow, sleep for about N seconds between checks
+    #[clap(
+        short = 's',
+        long,
+        value_name = "N",
+        default_value = "1",
+        value_parser = parse_interval
+    )]
+    sleep_interval: Duration,
+}
+
+/// What `--follow` keeps track of when a file is rotated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FollowMode {
+    /// Keep reading the opened file, even after it was renamed or removed
+    Descriptor,
+    /// Re-open the file by name when it is replaced
+    Name,
 }
 
 pub fn get_args() -> ProgResult<Config> {
-    let config = Config::parse();
+    let mut config = Config::parse();
+    config.stdout_is_terminal = io::stdout().is_terminal();
     Ok(config)
 }
 
-fn parse_line_count(val: &str) -> Result<usize, String> {
+impl Config {
+    /// The part of each file to print when `--tail` is not given.
+    fn head(&self) -> Head {
+        if let Some(pattern) = &self.until {
+            return Head::Until(pattern.clone());
+        }
+        if let Some(n) = self.chars {
+            let unit = if self.graphemes {
+                CharUnit::Grapheme
+            } else {
+                CharUnit::Scalar
+            };
+            return Head::Chars(n, unit, self.invalid_utf8);
+        }
+        match (self.bytes, &self.records) {
+            (Some(count), _) => Head::Bytes(count),
+            (None, Some(separator)) => Head::Records(self.lines, separator.clone()),
+            (None, None) => Head::Lines(self.lines),
+        }
+    }
+
+    /// The layout of `--hex` dumps.
+    fn hex_layout(&self) -> Option<Layout> {
+        self.hex.then_some(Layout {
+            group: self.hex_group,
+            columns: self.hex_columns,
+        })
+    }
+
+    /// What `--skip-lines` or `--skip-bytes` passes over.
+    fn skip(&self) -> Option<Skip> {
+        match (self.skip_lines, self.skip_bytes) {
+            (Some(n), _) => Some(Skip::Lines(n)),
+            (_, Some(n)) => Some(Skip::Bytes(n)),
+            _ => None,
+        }
+    }
+
+    /// The field delimiter for `--csv` and `--tsv`.
+    fn delimiter(&self) -> Option<u8> {
+        match (self.csv, self.tsv) {
+            (true, _) => Some(b','),
+            (_, true) => Some(b'\t'),
+            _ => None,
+        }
+    }
+
+    fn follow_mode(&self) -> Option<FollowMode> {
+        if self.follow_name {
+            Some(FollowMode::Name)
+        } else {
+            self.follow
+        }
+    }
+
+    fn retry(&self) -> bool {
+        self.retry || self.follow_name
+    }
+}
+
+fn parse_line_count(val: &str) -> Result<Count, HeadrError> {
+    val.parse()
+        .map_err(|_| HeadrError::InvalidArgument(format!("illegal line count -- {}", val)))
+}
+
+fn parse_byte_count(val: &str) -> Result<Count, HeadrError> {
+    val.parse()
+        .map_err(|_| HeadrError::InvalidArgument(format!("illegal byte count -- {}", val)))
+}
+
+fn parse_char_count(val: &str) -> Result<u64, HeadrError> {
+    match val.parse() {
+        Ok(Count::First(n)) => Ok(n),
+        _ => Err(HeadrError::InvalidArgument(format!(
+            "illegal character count -- {}",
+            val
+        ))),
+    }
+}
+
+fn parse_skip_count(val: &str) -> Result<u64, HeadrError> {
+    match val.parse() {
+        Ok(Count::First(n)) => Ok(n),
+        _ => Err(HeadrError::InvalidArgument(format!(
+            "illegal skip count -- {}",
+            val
+        ))),
+    }
+}
+
+fn parse_hex_size(val: &str) -> Result<usize, HeadrError> {
+    match val.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(HeadrError::InvalidArgument(format!(
+            "illegal hex size -- {}",
+            val
+        ))),
+    }
+}
+
+fn parse_tail_count(val: &str) -> Result<usize, HeadrError> {
     match val.parse() {
         Ok(n) if n > 0 => Ok(n),
-        _ => Err(format!("illegal line count -- {}", val)),
+        _ => Err(HeadrError::InvalidArgument(format!(
+            "illegal line count -- {}",
+            val
+        ))),
+    }
+}
+
+fn parse_regex(val: &str) -> Result<Regex, HeadrError> {
+    Regex::new(val).map_err(|err| HeadrError::InvalidArgument(err.to_string()))
+}
+
+/// Turns the escapes `\n`, `\r`, `\t`, `\0` and `\\` in a record separator into bytes.
+fn parse_separator(val: &str) -> Result<Separator, HeadrError> {
+    let invalid = |msg| HeadrError::InvalidArgument(format!("{} -- {}", msg, val));
+    let mut separator = Vec::new();
+    let mut bytes = val.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            separator.push(byte);
+            continue;
+        }
+        separator.push(match bytes.next() {
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b't') => b'\t',
+            Some(b'0') => b'\0',
+            Some(b'\\') => b'\\',
+            _ => return Err(invalid("invalid escape in record separator")),
+        });
     }
+    if separator.is_empty() {
+        return Err(invalid("empty record separator"));
+    }
+    Ok(separator)
 }
 
-fn parse_byte_count(val: &str) -> Result<usize, String> {
+fn parse_jobs(val: &str) -> Result<usize, HeadrError> {
     match val.parse() {
         Ok(n) if n > 0 => Ok(n),
-        _ => Err(format!("illegal byte count -- {}", val)),
+        _ => Err(HeadrError::InvalidArgument(format!(
+            "illegal job count -- {}",
+            val
+        ))),
+    }
+}
+
+/// Seconds that fit in a `Duration`, so neither negative nor infinite.
+fn parse_interval(val: &str) -> Result<Duration, HeadrError> {
+    match val.parse().map(Duration::try_from_secs_f64) {
+        Ok(Ok(interval)) => Ok(interval),
+        _ => Err(HeadrError::InvalidArgument(format!(
+            "invalid number of seconds -- {}",
+            val
+        ))),
     }
 }
 // #[test]
@@ -55,40 +409,139 @@ fn parse_byte_count(val: &str) -> Result<usize, String> {
 //     assert_eq!(res.unwrap_err().to_string(), "0".to_string());
 // }
 
-fn open(filename: &str) -> ProgResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+fn open(filename: &str, decompress: bool) -> Result<Box<dyn BufRead>, HeadrError> {
+    let file: Box<dyn BufRead> = match filename {
+        "-" => stdin::open(),
+        _ => match File::open(filename) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(err) => return Err(HeadrError::open(filename, err)),
+        },
+    };
+    if decompress {
+        decompress::decompress(file).map_err(|err| HeadrError::read(filename, err))
+    } else {
+        Ok(file)
     }
 }
 
-pub fn run(config: Config) -> ProgResult<()> {
-    let n_files = config.files.len();
-    for (file_num, filename) in config.files.iter().enumerate() {
-        match open(filename) {
-            Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(mut file) => {
-                if n_files > 1 {
-                    println!(
-                        "{}==> {} <==",
-                        if file_num > 0 { "\n" } else { "" },
-                        filename
-                    )
-                }
-                if let Some(num_bytes) = config.bytes {
-                    let mut handle = file.take(num_bytes as u64);
-                    let mut buffer = vec![0; num_bytes];
-                    let bytes_read = handle.read(&mut buffer)?;
-                    print!("{}", String::from_utf8_lossy(&buffer[..bytes_read]));
-                } else {
-                    let mut line = String::new();
-                    for _ in 0..config.lines {
-                        let bytes = file.read_line(&mut line)?;
-                        if bytes == 0 {
-                            break;
-                        }
-                        print!("{}", line);
-                        line.clear()
+/// Opens `filename` like `open`, positioned after `--skip-lines` or `--skip-bytes`,
+/// and returns the offset of that position.
+fn open_skipped(config: &Config, filename: &str) -> Result<(Box<dyn BufRead>, u64), HeadrError> {
+    match config.skip() {
+        Some(skip) => skip::open_skipped(filename, skip, config.line_index, config.decompress),
+        None => Ok((open(filename, config.decompress)?, 0)),
+    }
+}
+
+fn print_banner(filename: &str, first: bool) {
+    println!("{}==> {} <==", if first { "" } else { "\n" }, filename)
+}
+
+/// Prints the last `num_lines` lines of `file` and returns the number of bytes read.
+fn print_tail(
+    mut file: impl BufRead,
+    filename: &str,
+    num_lines: usize,
+    out: &mut impl Write,
+) -> Result<u64, HeadrError> {
+    let mut last_lines: VecDeque<Vec<u8>> = VecDeque::with_capacity(num_lines);
+    let mut bytes_read = 0;
+    loop {
+        let mut line = Vec::new();
+        let bytes = file
+            .read_until(b'\n', &mut line)
+            .map_err(|err| HeadrError::read(filename, err))?;
+        if bytes == 0 {
+            break;
+        }
+        bytes_read += bytes as u64;
+        if last_lines.len() == num_lines {
+            last_lines.pop_front();
+        }
+        last_lines.push_back(line);
+    }
+    for line in last_lines {
+        out.write_all(&line).map_err(HeadrError::Write)?;
+    }
+    out.flush().map_err(HeadrError::Write)?;
+    Ok(bytes_read)
+}
+
+/// Prints the head of `file`, which starts at `offset` in the input.
+fn print_head(
+    config: &Config,
+    mut file: impl BufRead,
+    filename: &str,
+    offset: u64,
+    out: &mut impl Write,
+) -> Result<(), HeadrError> {
+    if config.stdout_is_terminal && !config.force && !config.hex {
+        let block = file
+            .fill_buf()
+            .map_err(|err| HeadrError::read(filename, err))?;
+        if binary::looks_binary(block) {
+            eprintln!("headr: {}: binary file, use --force or --hex", filename);
+            return Ok(());
+        }
+    }
+    if let (Some(head), Some(tail)) = (config.head_lines, config.tail) {
+        let seek = !config.decompress && !config.archive;
+        return sandwich::print_head_and_tail(file, filename, head, tail, seek, out);
+    }
+    if let Some(num_lines) = config.tail {
+        print_tail(file, filename, num_lines, out)?;
+        return Ok(());
+    }
+    if let Some(delimiter) = config.delimiter() {
+        let count = config.lines;
+        return delimited::print_head(file, filename, delimiter, count, config.table, out);
+    }
+    let head = config.head();
+    if let Some(layout) = config.hex_layout() {
+        let mut dump = HexDump::new(out, layout, offset);
+        for chunk in chunks(file, head) {
+            let chunk = chunk.map_err(|err| HeadrError::read(filename, err))?;
+            dump.write_all(&chunk).map_err(HeadrError::Write)?;
+        }
+        return dump.finish().map_err(HeadrError::Write);
+    }
+    let lossy = matches!(head, Head::Bytes(_));
+    let mut partial_char = Vec::new();
+    for chunk in chunks(file, head) {
+        let chunk = chunk.map_err(|err| HeadrError::read(filename, err))?;
+        if lossy {
+            write_lossy(out, &mut partial_char, &chunk)
+        } else {
+            out.write_all(&chunk)
+        }
+        .map_err(HeadrError::Write)?;
+    }
+    if !partial_char.is_empty() {
+        write!(out, "{}", char::REPLACEMENT_CHARACTER).map_err(HeadrError::Write)?;
+    }
+    out.flush().map_err(HeadrError::Write)
+}
+
+/// Writes `bytes` like `String::from_utf8_lossy` would print them, carrying a
+/// character that is cut off at the end over to the next call in `partial_char`.
+fn write_lossy(out: &mut impl Write, partial_char: &mut Vec<u8>, bytes: &[u8]) -> io::Result<()> {
+    let mut data = std::mem::take(partial_char);
+    data.extend_from_slice(bytes);
+    let mut rest = &data[..];
+    while !rest.is_empty() {
+        match std::str::from_utf8(rest) {
+            Ok(text) => return out.write_all(text.as_bytes()),
+            Err(err) => {
+                let (valid, invalid) = rest.split_at(err.valid_up_to());
+                out.write_all(valid)?;
+                match err.error_len() {
+                    Some(len) => {
+                        write!(out, "{}", char::REPLACEMENT_CHARACTER)?;
+                        rest = &invalid[len..];
+                    }
+                    None => {
+                        partial_char.extend_from_slice(invalid);
+                        break;
                     }
                 }
             }
@@ -96,3 +549,70 @@ pub fn run(config: Config) -> ProgResult<()> {
     }
     Ok(())
 }
+
+/// Prints the head of every file in `config`.
+///
+/// Files that cannot be opened or read are reported on stderr and skipped;
+/// the returned exit code is a failure if that happened to any of them.
+pub fn run(config: Config) -> ProgResult<ExitCode> {
+    if let (Some(mode), Some(num_lines)) = (config.follow_mode(), config.tail) {
+        return follow::run(&config, mode, num_lines);
+    }
+    if config.jobs > 1 {
+        return parallel::run(&config, config.jobs);
+    }
+    let n_files = config.files.len();
+    let mut stdout = io::stdout();
+    let mut exit_code = ExitCode::SUCCESS;
+    let mut first_banner = true;
+    let mut banner = |name: &str| {
+        print_banner(name, first_banner);
+        first_banner = false;
+    };
+    for filename in &config.files {
+        let result = if config.archive {
+            archive::for_each_member(filename, |member, mut file| {
+                let offset = match config.skip() {
+                    Some(skip) => skip::skip_stream(&mut file, skip)
+                        .map_err(|err| HeadrError::read(filename, err))?,
+                    None => 0,
+                };
+                match member {
+                    Some(member) => {
+                        let name = format!("{}!{}", filename, member);
+                        banner(&name);
+                        print_head(&config, file, &name, offset, &mut stdout)
+                    }
+                    None => {
+                        if n_files > 1 {
+                            banner(filename)
+                        }
+                        print_head(&config, file, filename, offset, &mut stdout)
+                    }
+                }
+            })
+        } else {
+            open_skipped(&config, filename).and_then(|(file, offset)| {
+                if n_files > 1 {
+                    banner(filename)
+                }
+                print_head(&config, file, filename, offset, &mut stdout)
+            })
+        };
+        report(result, &mut exit_code)?;
+    }
+    Ok(exit_code)
+}
+
+/// Reports a failed file on stderr, or gives up when stdout cannot be written to.
+fn report(result: Result<(), HeadrError>, exit_code: &mut ExitCode) -> ProgResult<()> {
+    match result {
+        Ok(()) => Ok(()),
+        Err(HeadrError::Write(err)) => Err(Box::new(HeadrError::Write(err))),
+        Err(err) => {
+            eprintln!("{}", err);
+            *exit_code = ExitCode::FAILURE;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! `--line-index`: a sidecar file with the byte offsets of every
+//! `INTERVAL`th line, so skipping many lines needs only a seek and a short scan.
+//!
+//! The index for `FILE` is kept in `FILE.headr-index`. It starts with
+//! `MAGIC`, followed by the length and modification time of `FILE` when the
+//! index was built, and then the offsets, all as little-endian `u64`s. An
+//! index that does not match the file any more is rebuilt.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::time::UNIX_EPOCH;
+
+const MAGIC: &[u8; 8] = b"HEADRIX1";
+/// Lines between two offsets in the index.
+const INTERVAL: u64 = 1024;
+
+/// What an index has to have been built from to be used for a file.
+#[derive(Debug, PartialEq, Eq)]
+struct Source {
+    len: u64,
+    modified: u64,
+}
+
+impl Source {
+    fn of(file: &File) -> io::Result<Source> {
+        let metadata = file.metadata()?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        Ok(Source {
+            len: metadata.len(),
+            modified,
+        })
+    }
+}
+
+/// Seeks `file` as close before line `line` (counting from 0) as the index
+/// allows, and returns the number of lines that still have to be skipped.
+pub(crate) fn seek_line(file: &mut File, filename: &str, line: u64) -> io::Result<u64> {
+    let path = format!("{}.headr-index", filename);
+    let source = Source::of(file)?;
+    let offsets = match load(&path, &source) {
+        Some(offsets) => offsets,
+        None => {
+            let offsets = build(file)?;
+            // The index only saves time, so a file that cannot be written is no error.
+            let _ = save(&path, &source, &offsets);
+            offsets
+        }
+    };
+    let checkpoint = (line / INTERVAL).min(offsets.len() as u64 - 1);
+    file.seek(SeekFrom::Start(offsets[checkpoint as usize]))?;
+    Ok(line - checkpoint * INTERVAL)
+}
+
+/// Scans `file` for the offsets of lines 0, `INTERVAL`, `2 * INTERVAL`, ...
+fn build(file: &mut File) -> io::Result<Vec<u64>> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut offsets = vec![0];
+    let mut lines = 0;
+    let mut pos = 0;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let len = file.read(&mut buffer)?;
+        if len == 0 {
+            return Ok(offsets);
+        }
+        for (i, &byte) in buffer[..len].iter().enumerate() {
+            if byte == b'\n' {
+                lines += 1;
+                if lines % INTERVAL == 0 {
+                    offsets.push(pos + i as u64 + 1);
+                }
+            }
+        }
+        pos += len as u64;
+    }
+}
+
+fn load(path: &str, source: &Source) -> Option<Vec<u64>> {
+    let mut data = Vec::new();
+    File::open(path).ok()?.read_to_end(&mut data).ok()?;
+    let rest = data
+        .strip_prefix(MAGIC)
+        .filter(|rest| rest.len() % 8 == 0)?;
+    let mut numbers = rest
+        .chunks_exact(8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap_or_default()));
+    let built_from = Source {
+        len: numbers.next()?,
+        modified: numbers.next()?,
+    };
+    let offsets: Vec<u64> = numbers.collect();
+    Some(offsets).filter(|offsets| built_from == *source && !offsets.is_empty())
+}
+
+fn save(path: &str, source: &Source, offsets: &[u64]) -> io::Result<()> {
+    // Written next to the index and renamed, so readers never see half of one.
+    let partial = format!("{}.tmp", path);
+    let mut out = BufWriter::new(File::create(&partial)?);
+    out.write_all(MAGIC)?;
+    for number in [source.len, source.modified].iter().chain(offsets) {
+        out.write_all(&number.to_le_bytes())?;
+    }
+    out.flush()?;
+    drop(out);
+    fs::rename(&partial, path)
+}
@@ -1,6 +1,11 @@
-fn main() {
-    if let Err(e) = headr::get_args().and_then(headr::run) {
-        eprintln!("Main speaking: {}", e);
-        std::process::exit(1)
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    match headr::get_args().and_then(headr::run) {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            eprintln!("Main speaking: {}", e);
+            ExitCode::FAILURE
+        }
     }
 }
@@ -0,0 +1,125 @@
+//! `-j N`: open and read several files at the same time.
+//!
+//! Worker threads pass each file's head in chunks to the main thread, which
+//! prints the files in argument order, streaming the oldest one as it comes.
+//! Every file has a slot of at most `SLOT_CHUNKS` chunks of `CHUNK_SIZE`
+//! bytes, and at most `2 * jobs` files are queued at once, so a slow file early
+//! on stalls the workers instead of growing the backlog.
+
+use crate::{open_skipped, print_banner, print_head, report, Config, HeadrError, ProgResult};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Mutex;
+use std::thread;
+
+/// Bytes a worker collects before passing them on.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Chunks a slot holds while the main thread is still printing earlier files.
+/// With the chunk a worker is filling, a file holds 256 KiB at most.
+const SLOT_CHUNKS: usize = 3;
+
+/// What a worker passes on about the file it reads.
+enum Piece {
+    /// The file could be opened, so it gets a banner.
+    Opened,
+    Output(Vec<u8>),
+    /// The file has been read, as far as it could be.
+    Done(Result<(), HeadrError>),
+}
+
+/// Passes what is written to it on to a slot, a chunk at a time.
+struct SlotWriter<'a> {
+    slot: &'a SyncSender<Piece>,
+    chunk: Vec<u8>,
+}
+
+impl Write for SlotWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(CHUNK_SIZE - self.chunk.len());
+        self.chunk.extend_from_slice(&buf[..len]);
+        if self.chunk.len() == CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_SIZE));
+        self.slot
+            .send(Piece::Output(chunk))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+fn fetch(config: &Config, filename: &str, slot: &SyncSender<Piece>) {
+    let result = open_skipped(config, filename).and_then(|(file, offset)| {
+        let _ = slot.send(Piece::Opened);
+        let mut out = SlotWriter {
+            slot,
+            chunk: Vec::with_capacity(CHUNK_SIZE),
+        };
+        // What was read before an error is still printed.
+        let result = print_head(config, file, filename, offset, &mut out);
+        let flushed = out.flush().map_err(HeadrError::Write);
+        result.and(flushed)
+    });
+    let _ = slot.send(Piece::Done(result));
+}
+
+pub(crate) fn run(config: &Config, jobs: usize) -> ProgResult<ExitCode> {
+    let files = &config.files;
+    let window = 2 * jobs;
+    let (job_tx, job_rx) = mpsc::sync_channel::<(usize, SyncSender<Piece>)>(window);
+    let job_rx = Mutex::new(job_rx);
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(files.len()) {
+            let job_rx = &job_rx;
+            scope.spawn(move || loop {
+                let next = job_rx.lock().map(|rx| rx.recv());
+                match next {
+                    Ok(Ok((index, slot))) => fetch(config, &files[index], &slot),
+                    _ => break,
+                }
+            });
+        }
+        // Owned by this closure, so the workers stop even when it returns early.
+        let job_tx = job_tx;
+
+        let mut stdout = io::stdout();
+        let mut exit_code = ExitCode::SUCCESS;
+        let mut first_banner = true;
+        let mut slots: VecDeque<Receiver<Piece>> = VecDeque::new();
+        let mut queued = 0;
+        for next in 0..files.len() {
+            while queued < files.len() && queued < next + window {
+                let (slot_tx, slot_rx) = mpsc::sync_channel(SLOT_CHUNKS);
+                // Cannot block: never more than `window` jobs are outstanding.
+                let _ = job_tx.send((queued, slot_tx));
+                slots.push_back(slot_rx);
+                queued += 1;
+            }
+            let slot = slots.pop_front().ok_or("no slot for the next file")?;
+            let mut result = None;
+            for piece in slot {
+                match piece {
+                    Piece::Opened if files.len() > 1 => {
+                        print_banner(&files[next], first_banner);
+                        first_banner = false;
+                    }
+                    Piece::Opened => {}
+                    Piece::Output(chunk) => stdout.write_all(&chunk).map_err(HeadrError::Write)?,
+                    Piece::Done(done) => result = Some(done),
+                }
+            }
+            stdout.flush().map_err(HeadrError::Write)?;
+            report(result.ok_or("worker thread died")?, &mut exit_code)?;
+        }
+        Ok(exit_code)
+    })
+}
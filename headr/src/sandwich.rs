@@ -0,0 +1,139 @@
+//! `--head N --tail N`: the first and last lines of a file with the number of
+//! lines left out in between.
+//!
+//! Pipes are read once, keeping the last lines in a ring buffer. Regular files
+//! are searched backwards from the end for the start of the last lines instead,
+//! so the middle only has its newlines counted.
+
+use crate::HeadrError;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/// Prints the first `head` and last `tail` lines of `file`.
+///
+/// Unless `seek` is false, a regular file named `filename` is opened again
+/// to be read from the end, since `file` itself cannot seek.
+pub(crate) fn print_head_and_tail(
+    mut file: impl BufRead,
+    filename: &str,
+    head: usize,
+    tail: usize,
+    seek: bool,
+    out: &mut impl Write,
+) -> Result<(), HeadrError> {
+    let regular =
+        seek && filename != "-" && fs::metadata(filename).is_ok_and(|metadata| metadata.is_file());
+    if regular {
+        let file = File::open(filename).map_err(|err| HeadrError::open(filename, err))?;
+        return print_seeking(file, filename, head, tail, out);
+    }
+
+    let read_error = |err| HeadrError::read(filename, err);
+    for _ in 0..head {
+        match read_line(&mut file).map_err(read_error)? {
+            Some(line) => out.write_all(&line).map_err(HeadrError::Write)?,
+            None => return out.flush().map_err(HeadrError::Write),
+        }
+    }
+    let mut last_lines: VecDeque<Vec<u8>> = VecDeque::with_capacity(tail);
+    let mut omitted = 0;
+    while let Some(line) = read_line(&mut file).map_err(read_error)? {
+        if last_lines.len() == tail {
+            last_lines.pop_front();
+            omitted += 1;
+        }
+        last_lines.push_back(line);
+    }
+    write_marker(out, omitted).map_err(HeadrError::Write)?;
+    for line in last_lines {
+        out.write_all(&line).map_err(HeadrError::Write)?;
+    }
+    out.flush().map_err(HeadrError::Write)
+}
+
+fn print_seeking(
+    file: File,
+    filename: &str,
+    head: usize,
+    tail: usize,
+    out: &mut impl Write,
+) -> Result<(), HeadrError> {
+    let read_error = |err| HeadrError::read(filename, err);
+    let mut reader = BufReader::new(file);
+    let mut head_end = 0;
+    for _ in 0..head {
+        let mut line = Vec::new();
+        let len = reader.read_until(b'\n', &mut line).map_err(read_error)?;
+        if len == 0 {
+            return out.flush().map_err(HeadrError::Write);
+        }
+        head_end += len as u64;
+        out.write_all(&line).map_err(HeadrError::Write)?;
+    }
+
+    let tail_start = find_tail_start(reader.get_mut(), tail).map_err(read_error)?;
+    reader.seek(SeekFrom::Start(head_end)).map_err(read_error)?;
+    if tail_start > head_end {
+        let omitted =
+            count_newlines(reader.by_ref().take(tail_start - head_end)).map_err(read_error)?;
+        write_marker(out, omitted).map_err(HeadrError::Write)?;
+    }
+    io::copy(&mut reader, out).map_err(read_error)?;
+    out.flush().map_err(HeadrError::Write)
+}
+
+/// Returns the offset of the first of the last `tail` lines of `file`.
+fn find_tail_start(file: &mut File, tail: usize) -> io::Result<u64> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut end = len;
+    let mut block = Vec::new();
+    let mut newlines = 0;
+    while end > 0 {
+        let start = end.saturating_sub(BLOCK_SIZE);
+        block.resize((end - start) as usize, 0);
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut block)?;
+        for (i, &byte) in block.iter().enumerate().rev() {
+            let line_start = start + i as u64 + 1;
+            // A newline at the very end finishes the last line rather than starting one.
+            if byte == b'\n' && line_start < len {
+                newlines += 1;
+                if newlines == tail {
+                    return Ok(line_start);
+                }
+            }
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+fn read_line(file: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    file.read_until(b'\n', &mut line)?;
+    Ok(Some(line).filter(|line| !line.is_empty()))
+}
+
+fn count_newlines(mut reader: impl BufRead) -> io::Result<u64> {
+    let mut newlines = 0;
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            return Ok(newlines);
+        }
+        newlines += buffer.iter().filter(|&&byte| byte == b'\n').count() as u64;
+        let len = buffer.len();
+        reader.consume(len);
+    }
+}
+
+fn write_marker(out: &mut impl Write, omitted: u64) -> io::Result<()> {
+    match omitted {
+        0 => Ok(()),
+        1 => writeln!(out, "… (1 line omitted) …"),
+        _ => writeln!(out, "… ({} lines omitted) …", omitted),
+    }
+}
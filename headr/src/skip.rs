@@ -0,0 +1,77 @@
+//! `--skip-lines` and `--skip-bytes`: start the head further into the input.
+//!
+//! Regular files are seeked past skipped bytes. Skipped lines have to be
+//! scanned for, unless a line index next to the file says where they end.
+
+use crate::{line_index, open, HeadrError};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+
+/// How much of the input to pass over before the head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Skip {
+    Lines(u64),
+    Bytes(u64),
+}
+
+/// Opens `filename` positioned right after the skipped part, and returns the
+/// offset of that position in the input.
+pub(crate) fn open_skipped(
+    filename: &str,
+    skip: Skip,
+    use_index: bool,
+    decompress: bool,
+) -> Result<(Box<dyn BufRead>, u64), HeadrError> {
+    let regular = filename != "-"
+        && !decompress
+        && fs::metadata(filename).is_ok_and(|metadata| metadata.is_file());
+    if !regular {
+        let mut file = open(filename, decompress)?;
+        let offset = skip_stream(&mut file, skip).map_err(|err| HeadrError::read(filename, err))?;
+        return Ok((file, offset));
+    }
+
+    let read_error = |err| HeadrError::read(filename, err);
+    let mut file = File::open(filename).map_err(|err| HeadrError::open(filename, err))?;
+    let lines = match skip {
+        Skip::Bytes(n) => {
+            file.seek(SeekFrom::Start(n)).map_err(read_error)?;
+            0
+        }
+        Skip::Lines(n) if use_index => {
+            line_index::seek_line(&mut file, filename, n).map_err(read_error)?
+        }
+        Skip::Lines(n) => n,
+    };
+    let position = file.stream_position().map_err(read_error)?;
+    let mut file = BufReader::new(file);
+    let scanned = skip_stream(&mut file, Skip::Lines(lines)).map_err(read_error)?;
+    Ok((Box::new(file), position + scanned))
+}
+
+/// Reads past the skipped part of `reader`, and returns the number of bytes
+/// that took.
+pub(crate) fn skip_stream(reader: &mut impl BufRead, skip: Skip) -> io::Result<u64> {
+    match skip {
+        Skip::Bytes(n) => io::copy(&mut reader.by_ref().take(n), &mut io::sink()),
+        Skip::Lines(mut n) => {
+            let mut skipped = 0;
+            while n > 0 {
+                let buffer = reader.fill_buf()?;
+                if buffer.is_empty() {
+                    break;
+                }
+                let len = match buffer.iter().position(|&byte| byte == b'\n') {
+                    Some(newline) => {
+                        n -= 1;
+                        newline + 1
+                    }
+                    None => buffer.len(),
+                };
+                reader.consume(len);
+                skipped += len as u64;
+            }
+            Ok(skipped)
+        }
+    }
+}
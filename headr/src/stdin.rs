@@ -0,0 +1,55 @@
+//! Standard input that is left positioned right after the data headr consumed,
+//! so that `{ headr -n 1; cat; } < file` behaves like it does with GNU head.
+
+use std::io::{self, BufRead, BufReader, Read};
+
+#[cfg(unix)]
+pub(crate) fn open() -> Box<dyn BufRead> {
+    use std::os::fd::AsFd;
+
+    // The duplicate shares its file offset with descriptor 0.
+    match io::stdin().as_fd().try_clone_to_owned() {
+        Ok(fd) => Box::new(Rewinding(BufReader::new(fd.into()))),
+        Err(_) => Box::new(BufReader::new(io::stdin())),
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn open() -> Box<dyn BufRead> {
+    Box::new(BufReader::new(io::stdin()))
+}
+
+/// Seeks back over whatever was buffered but not consumed when dropped.
+#[cfg(unix)]
+struct Rewinding(BufReader<std::fs::File>);
+
+#[cfg(unix)]
+impl Read for Rewinding {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+#[cfg(unix)]
+impl BufRead for Rewinding {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.0.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.0.consume(amt)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Rewinding {
+    fn drop(&mut self) {
+        use std::io::{Seek, SeekFrom};
+
+        let unread = self.0.buffer().len() as i64;
+        if unread > 0 {
+            // Pipes and terminals cannot seek, their extra data is simply gone.
+            let _ = self.0.get_mut().seek(SeekFrom::Current(-unread));
+        }
+    }
+}
@@ -1,10 +1,15 @@
-use assert_cmd::Command;
+use assert_cmd::{cargo::CommandCargoExt, Command};
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
 use std::io::prelude::*;
 use std::{
     error::Error,
-    fs::{self, File},
+    fs::{self, File, OpenOptions},
+    path::Path,
+    process::{self, ExitStatus, Output, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
 
 type TestResult = Result<(), Box<dyn Error>>;
@@ -41,7 +46,7 @@ fn dies_bad_bytes() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal byte count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-c", &bad, EMPTY])
+        .args(["-c", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -55,7 +60,7 @@ fn dies_bad_lines() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal line count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-n", &bad, EMPTY])
+        .args(["-n", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -66,11 +71,11 @@ fn dies_bad_lines() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn dies_bytes_and_lines() -> TestResult {
-    let msg = "The argument '--lines <LINES>' cannot be \
+    let msg = "the argument '--lines <LINES>' cannot be \
                used with '--bytes <BYTES>'";
 
     Command::cargo_bin(PRG)?
-        .args(&["-n", "1", "-c", "2"])
+        .args(["-n", "1", "-c", "2"])
         .assert()
         .failure()
         .stderr(predicate::str::contains(msg));
@@ -86,7 +91,28 @@ fn skips_bad_file() -> TestResult {
     Command::cargo_bin(PRG)?
         .args([EMPTY, &bad, ONE])
         .assert()
-        .stderr(predicate::str::is_match(expected)?);
+        .failure()
+        .code(1)
+        .stderr(predicate::str::is_match(expected)?)
+        .stdout(predicate::str::contains("Öne line, four words."));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_unreadable_file() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["./tests/inputs", TWO])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::starts_with(
+            "error reading ./tests/inputs: Is a directory",
+        ))
+        .stdout(
+            "==> ./tests/inputs <==\n\n==> ./tests/inputs/two.txt <==\nTwo lines.\nFour words.\n",
+        );
 
     Ok(())
 }
@@ -103,17 +129,13 @@ fn run(args: &[&str], expected_file: &str) -> TestResult {
         .args(args)
         .assert()
         .success()
-        .stdout(predicate::eq(&expected.as_bytes() as &[u8]));
+        .stdout(predicate::eq(expected.as_bytes() as &[u8]));
 
     Ok(())
 }
 
 // --------------------------------------------------
-fn run_stdin(
-    args: &[&str],
-    input_file: &str,
-    expected_file: &str,
-) -> TestResult {
+fn run_stdin(args: &[&str], input_file: &str, expected_file: &str) -> TestResult {
     // Extra work here due to lossy UTF
     let mut file = File::open(expected_file)?;
     let mut buffer = Vec::new();
@@ -125,7 +147,7 @@ fn run_stdin(
         .write_stdin(input)
         .args(args)
         .assert()
-        .stdout(predicate::eq(&expected.as_bytes() as &[u8]));
+        .stdout(predicate::eq(expected.as_bytes() as &[u8]));
 
     Ok(())
 }
@@ -419,3 +441,1105 @@ fn multiple_files_c4() -> TestResult {
         "tests/expected/all.c4.out",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn ten_t2() -> TestResult {
+    run(&[TEN, "--tail", "2"], "tests/expected/ten.txt.t2.out")
+}
+
+#[test]
+fn three_t2() -> TestResult {
+    run(&[THREE, "--tail", "2"], "tests/expected/three.txt.t2.out")
+}
+
+#[test]
+fn ten_t2_stdin() -> TestResult {
+    run_stdin(&["--tail", "2"], TEN, "tests/expected/ten.txt.t2.out")
+}
+
+#[test]
+fn multiple_files_t2() -> TestResult {
+    run(
+        &[EMPTY, ONE, TWO, THREE, TEN, "--tail", "2"],
+        "tests/expected/all.t2.out",
+    )
+}
+
+#[test]
+fn dies_follow_without_tail() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--tail <N>"));
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_sleep_interval() -> TestResult {
+    for interval in ["inf", "1e300", "NaN"] {
+        Command::cargo_bin(PRG)?
+            .args(["--tail", "1", "-f", "-s", interval, TEN])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "invalid number of seconds -- {}",
+                interval
+            )));
+    }
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn append(path: &Path, text: &str) -> TestResult {
+    OpenOptions::new()
+        .append(true)
+        .open(path)?
+        .write_all(text.as_bytes())?;
+    Ok(())
+}
+
+/// A running `headr --follow`, which stops once the helper process passed as
+/// `--pid` is killed.
+struct Following {
+    headr: process::Child,
+    watched: process::Child,
+    stdout: Arc<Mutex<Vec<u8>>>,
+    reader: thread::JoinHandle<()>,
+}
+
+impl Following {
+    fn start(args: &[&str]) -> Result<Following, Box<dyn Error>> {
+        let watched = process::Command::new("sleep").arg("30").spawn()?;
+        let mut headr = process::Command::cargo_bin(PRG)?
+            .args(["-s", "0.05", "--pid", &watched.id().to_string()])
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut pipe = headr.stdout.take().ok_or("no stdout")?;
+        let stdout = Arc::new(Mutex::new(Vec::new()));
+        let output = Arc::clone(&stdout);
+        let reader = thread::spawn(move || {
+            let mut buffer = [0; 1024];
+            while let Ok(len @ 1..) = pipe.read(&mut buffer) {
+                output.lock().unwrap().extend_from_slice(&buffer[..len]);
+            }
+        });
+        Ok(Following {
+            headr,
+            watched,
+            stdout,
+            reader,
+        })
+    }
+
+    /// Waits until the output so far ends with `expected`.
+    fn wait_for(&self, expected: &str) -> TestResult {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let stdout = self.stdout.lock().unwrap();
+            if stdout.ends_with(expected.as_bytes()) {
+                return Ok(());
+            }
+            if Instant::now() > deadline {
+                return Err(format!(
+                    "timed out waiting for {:?}, got {:?}",
+                    expected,
+                    String::from_utf8_lossy(&stdout)
+                )
+                .into());
+            }
+            drop(stdout);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Stops headr and returns its exit status, output and error output.
+    fn stop(mut self) -> Result<(ExitStatus, String, String), Box<dyn Error>> {
+        self.watched.kill()?;
+        self.watched.wait()?;
+        let output = self.headr.wait_with_output()?;
+        self.reader.join().map_err(|_| "reader thread panicked")?;
+        let stdout = self.stdout.lock().unwrap().clone();
+        Ok((
+            output.status,
+            String::from_utf8(stdout)?,
+            String::from_utf8(output.stderr)?,
+        ))
+    }
+}
+
+#[test]
+fn dies_follow_options_without_follow() -> TestResult {
+    for args in [["--pid", "1"], ["--retry", "--tail=1"]] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .arg(TEN)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("<--follow[=<HOW>]|-F>"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn follows_appended_lines() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let log = dir.path().join("log.txt");
+    fs::write(&log, "one\ntwo\nthree\n")?;
+
+    let headr = Following::start(&["--tail", "1", "-f", log.to_str().unwrap()])?;
+    headr.wait_for("three\n")?;
+    append(&log, "four\n")?;
+    headr.wait_for("four\n")?;
+    append(&log, "five")?;
+    headr.wait_for("five")?;
+    let (status, stdout, _) = headr.stop()?;
+    assert!(status.success());
+    assert_eq!(stdout, "three\nfour\nfive");
+
+    Ok(())
+}
+
+#[test]
+fn follows_truncated_file() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let log = dir.path().join("log.txt");
+    fs::write(&log, "one\ntwo\nthree\n")?;
+
+    let headr = Following::start(&["--tail", "2", "--follow", log.to_str().unwrap()])?;
+    headr.wait_for("three\n")?;
+    fs::write(&log, "new\n")?;
+    headr.wait_for("new\n")?;
+    let (_, stdout, stderr) = headr.stop()?;
+    assert_eq!(stdout, "two\nthree\nnew\n");
+    assert!(stderr.contains("file truncated"));
+
+    Ok(())
+}
+
+#[test]
+fn follows_rotated_file_by_name() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let log = dir.path().join("log.txt");
+    fs::write(&log, "one\ntwo\n")?;
+
+    let headr = Following::start(&["--tail", "1", "-F", log.to_str().unwrap()])?;
+    headr.wait_for("two\n")?;
+    append(&log, "three\n")?;
+    headr.wait_for("three\n")?;
+    fs::rename(&log, dir.path().join("log.txt.1"))?;
+    fs::write(&log, "fresh\n")?;
+    headr.wait_for("fresh\n")?;
+    let (_, stdout, stderr) = headr.stop()?;
+    assert_eq!(stdout, "two\nthree\nfresh\n");
+    assert!(stderr.contains("has been replaced"));
+
+    Ok(())
+}
+
+#[test]
+fn follows_descriptor_after_rotation() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let log = dir.path().join("log.txt");
+    let rotated = dir.path().join("log.txt.1");
+    fs::write(&log, "one\n")?;
+
+    let headr = Following::start(&["--tail", "1", "-f", log.to_str().unwrap()])?;
+    headr.wait_for("one\n")?;
+    fs::rename(&log, &rotated)?;
+    fs::write(&log, "fresh\n")?;
+    append(&rotated, "two\n")?;
+    headr.wait_for("two\n")?;
+    let (_, stdout, _) = headr.stop()?;
+    assert_eq!(stdout, "one\ntwo\n");
+
+    Ok(())
+}
+
+#[test]
+fn follows_multiple_files() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let first = dir.path().join("first.txt");
+    let second = dir.path().join("second.txt");
+    fs::write(&first, "a\n")?;
+    fs::write(&second, "b\n")?;
+    let (first_name, second_name) = (first.to_str().unwrap(), second.to_str().unwrap());
+
+    let headr = Following::start(&["--tail", "1", "-f", first_name, second_name])?;
+    headr.wait_for("b\n")?;
+    append(&second, "c\n")?;
+    headr.wait_for("c\n")?;
+    append(&first, "d\n")?;
+    headr.wait_for("d\n")?;
+    let (_, stdout, _) = headr.stop()?;
+    let expected = format!(
+        "==> {0} <==\na\n\n==> {1} <==\nb\nc\n\n==> {0} <==\nd\n",
+        first_name, second_name
+    );
+    assert_eq!(stdout, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_sh(script: &str, expected: &str) -> TestResult {
+    let headr = assert_cmd::cargo::cargo_bin(PRG);
+    Command::new("sh")
+        .env("HEADR", headr)
+        .args(["-c", script])
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
+#[test]
+fn leaves_stdin_after_lines() -> TestResult {
+    let script = format!(r#"{{ "$HEADR" -n 2; echo ---; cat; }} < {}"#, TEN);
+    let expected = fs::read_to_string(TEN)?.replacen("two\n", "two\n---\n", 1);
+    run_sh(&script, &expected)
+}
+
+#[test]
+fn leaves_stdin_after_bytes() -> TestResult {
+    let script = format!(r#"{{ "$HEADR" -c 5; echo; cat; }} < {}"#, TEN);
+    let expected = fs::read_to_string(TEN)?.replacen("one\nt", "one\nt\n", 1);
+    run_sh(&script, &expected)
+}
+
+#[test]
+fn consumes_piped_stdin() -> TestResult {
+    let script = format!(r#"cat {} | {{ "$HEADR" -n 2; cat; }}"#, TEN);
+    run_sh(&script, "one\ntwo\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn ten_all_but_last_n2() -> TestResult {
+    run(&[TEN, "-n", "-2"], "tests/expected/ten.txt.n-2.out")
+}
+
+#[test]
+fn three_all_but_last_n2() -> TestResult {
+    run(&[THREE, "-n", "-2"], "tests/expected/three.txt.n-2.out")
+}
+
+#[test]
+fn one_all_but_last_c3() -> TestResult {
+    run(&[ONE, "-c", "-3"], "tests/expected/one.txt.c-3.out")
+}
+
+#[test]
+fn ten_all_but_last_c3_stdin() -> TestResult {
+    run_stdin(&["-c", "-3"], TEN, "tests/expected/ten.txt.c-3.out")
+}
+
+#[test]
+fn multiple_files_all_but_last_n2() -> TestResult {
+    run(
+        &[EMPTY, ONE, TWO, THREE, TEN, "-n", "-2"],
+        "tests/expected/all.n-2.out",
+    )
+}
+
+#[test]
+fn multiple_files_all_but_last_c3() -> TestResult {
+    run(
+        &["-c", "-3", EMPTY, ONE, TWO, THREE, TEN],
+        "tests/expected/all.c-3.out",
+    )
+}
+
+#[test]
+fn ten_c_suffix() -> TestResult {
+    run(&[TEN, "-c", "1K"], "tests/expected/ten.txt.out")
+}
+
+// --------------------------------------------------
+const ENTRIES: &str = "./tests/inputs/entries.log";
+
+#[test]
+fn until_regex() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--until", "^  at", ENTRIES])
+        .assert()
+        .success()
+        .stdout(
+            "2024-01-01 12:00 start\n  loading config\n---\n2024-01-01 12:01 error\n  at main.rs:10\n",
+        );
+
+    Ok(())
+}
+
+#[test]
+fn until_regex_multiple_files() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--until", "w", TEN, TWO])
+        .assert()
+        .success()
+        .stdout(format!(
+            "==> {} <==\none\ntwo\n\n==> {} <==\nTwo lines.\n",
+            TEN, TWO
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_until_regex() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--until", "(", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("regex parse error"));
+
+    Ok(())
+}
+
+#[test]
+fn dies_until_with_records() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--until", "w", "--records", "---\\n", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
+
+#[test]
+fn records() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--records", "---\\n", "-n", "1", ENTRIES])
+        .assert()
+        .success()
+        .stdout("2024-01-01 12:00 start\n  loading config\n---\n");
+
+    Ok(())
+}
+
+#[test]
+fn records_all_but_last_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--records", "---\\n", "-n", "-1"])
+        .write_stdin(fs::read_to_string(ENTRIES)?)
+        .assert()
+        .success()
+        .stdout(
+            "2024-01-01 12:00 start\n  loading config\n---\n\
+             2024-01-01 12:01 error\n  at main.rs:10\n  at lib.rs:20\n---\n",
+        );
+
+    Ok(())
+}
+
+#[test]
+fn dies_empty_record_separator() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--records", "", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("empty record separator"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(feature = "gzip")]
+fn decompress_gzip() -> TestResult {
+    run(
+        &["-z", "-n", "2", "./tests/inputs/ten.txt.gz"],
+        "tests/expected/ten.txt.n2.out",
+    )
+}
+
+#[test]
+#[cfg(feature = "zstd")]
+fn decompress_zstd_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--decompress", "-c", "4"])
+        .write_stdin(fs::read("./tests/inputs/ten.txt.zst")?)
+        .assert()
+        .success()
+        .stdout(fs::read_to_string("tests/expected/ten.txt.c4.out")?);
+
+    Ok(())
+}
+
+#[test]
+fn decompress_plain_file() -> TestResult {
+    run(&["-z", TEN], "tests/expected/ten.txt.out")
+}
+
+#[test]
+#[cfg(all(feature = "tar", feature = "gzip"))]
+fn archive_tar_gz() -> TestResult {
+    let archive = "./tests/inputs/members.tar.gz";
+    Command::cargo_bin(PRG)?
+        .args(["--archive", "-n", "1", archive])
+        .assert()
+        .success()
+        .stdout(format!(
+            "==> {0}!one.txt <==\nÖne line, four words.\n\n\
+             ==> {0}!two.txt <==\nTwo lines.\n\n\
+             ==> {0}!ten.txt <==\none\n",
+            archive
+        ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn archive_zip_and_plain_file() -> TestResult {
+    let archive = "./tests/inputs/members.zip";
+    Command::cargo_bin(PRG)?
+        .args(["--archive", "-n", "1", archive, TEN])
+        .assert()
+        .success()
+        .stdout(format!(
+            "==> {0}!three.txt <==\nThree\r\n\n\
+             ==> {0}!two.txt <==\nTwo lines.\n\n\
+             ==> {1} <==\none\n",
+            archive, TEN
+        ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn archive_zip_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--archive", "-c", "3"])
+        .write_stdin(fs::read("./tests/inputs/members.zip")?)
+        .assert()
+        .success()
+        .stdout("==> -!three.txt <==\nThr\n==> -!two.txt <==\nTwo");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+const PEOPLE: &str = "./tests/inputs/people.csv";
+const CITIES: &str = "./tests/inputs/cities.tsv";
+
+#[test]
+fn csv_keeps_quoted_newlines() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--csv", "-n", "2", PEOPLE])
+        .assert()
+        .success()
+        .stdout("name,age,note\nAlice,30,\"likes\nnewlines\"\nBob,4,plain\n");
+
+    Ok(())
+}
+
+#[test]
+fn csv_all_but_last_keeps_header() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--csv", "-n", "-4", PEOPLE])
+        .assert()
+        .success()
+        .stdout("name,age,note\n");
+
+    Ok(())
+}
+
+#[test]
+fn csv_table() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--csv", "--table", "-n", "3", PEOPLE])
+        .assert()
+        .success()
+        .stdout(
+            "name        age  note\n\
+             ----------  ---  ---------------\n\
+             Alice       30   likes\\nnewlines\n\
+             Bob         4    plain\n\
+             Carol, Jr.  55   x\n",
+        );
+
+    Ok(())
+}
+
+#[test]
+fn tsv_table_multiple_files() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--tsv", "--table", "-n", "2", CITIES, EMPTY])
+        .assert()
+        .success()
+        .stdout(format!(
+            "==> {} <==\nid  city\n--  ---------\n1   Berlin\n2   São Paulo\n\n==> {} <==\n",
+            CITIES, EMPTY
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn csv_keeps_record_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--csv", "-n", "1"])
+        .write_stdin("a,\"b\"\r\n\"x\" ,y\r\nz,w\r\n")
+        .assert()
+        .success()
+        .stdout("a,\"b\"\r\n\"x\" ,y\r\n");
+
+    Ok(())
+}
+
+#[test]
+fn csv_leaves_stdin_after_records() -> TestResult {
+    let script = format!(r#"{{ "$HEADR" --csv -n 1; echo ---; cat; }} < {}"#, PEOPLE);
+    let expected = fs::read_to_string(PEOPLE)?.replacen("newlines\"\n", "newlines\"\n---\n", 1);
+    run_sh(&script, &expected)
+}
+
+#[test]
+fn csv_table_wide_characters() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--csv", "--table"])
+        .write_stdin("name,city\n日本,Tokyo\nab,x\n")
+        .assert()
+        .success()
+        .stdout("name  city\n----  -----\n日本  Tokyo\nab    x\n");
+
+    Ok(())
+}
+
+#[test]
+fn dies_table_without_csv() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--table", PEOPLE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("<--csv|--tsv>"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_jobs() -> TestResult {
+    run(
+        &["-j", "4", EMPTY, ONE, TWO, THREE, TEN],
+        "tests/expected/all.out",
+    )
+}
+
+#[test]
+fn multiple_files_jobs_n2() -> TestResult {
+    run(
+        &["-j", "2", "-n", "2", EMPTY, ONE, TWO, THREE, TEN],
+        "tests/expected/all.n2.out",
+    )
+}
+
+#[test]
+fn multiple_files_jobs_c4() -> TestResult {
+    run(
+        &["-j", "3", "-c", "4", EMPTY, ONE, TWO, THREE, TEN],
+        "tests/expected/all.c4.out",
+    )
+}
+
+#[test]
+fn jobs_keep_order_around_bad_file() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = fs::read_to_string("tests/expected/all.n2.out")?;
+    let output = Command::cargo_bin(PRG)?
+        .args(["-j", "2", "-n", "2", EMPTY, ONE, TWO, &bad, THREE, TEN])
+        .output()?;
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+    assert!(String::from_utf8(output.stderr)?.contains(&bad));
+
+    Ok(())
+}
+
+#[test]
+fn jobs_stream_large_heads() -> TestResult {
+    // Heads much larger than a slot, so workers have to wait for the printer.
+    let dir = tempfile::tempdir()?;
+    let mut names = Vec::new();
+    for num in 0..5 {
+        let path = dir.path().join(format!("{}.txt", num));
+        let line = format!("file {} line\n", num);
+        fs::write(&path, line.repeat(100_000))?;
+        names.push(path.to_str().unwrap().to_string());
+    }
+    let expected = Command::cargo_bin(PRG)?
+        .args(["-c", "1000000"])
+        .args(&names)
+        .output()?;
+    assert!(expected.status.success());
+    Command::cargo_bin(PRG)?
+        .args(["-j", "2", "-c", "1000000"])
+        .args(&names)
+        .assert()
+        .success()
+        .stdout(expected.stdout);
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_jobs() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-j", "0", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal job count -- 0"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+const GRAPHEMES: &str = "./tests/inputs/graphemes.txt";
+
+#[test]
+fn chars() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "3", GRAPHEMES])
+        .assert()
+        .success()
+        .stdout("ne\u{301}");
+
+    Ok(())
+}
+
+#[test]
+fn chars_graphemes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "6", "--graphemes", GRAPHEMES])
+        .assert()
+        .success()
+        .stdout("ne\u{301}e\u{301} \u{1F468}\u{200D}\u{1F469} ");
+
+    Ok(())
+}
+
+#[test]
+fn chars_invalid_utf8() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "10", "--graphemes", GRAPHEMES])
+        .assert()
+        .success()
+        .stdout("ne\u{301}e\u{301} \u{1F468}\u{200D}\u{1F469} x\u{FFFD}yz");
+    Command::cargo_bin(PRG)?
+        .args([
+            "--chars",
+            "10",
+            "--graphemes",
+            "--invalid-utf8=skip",
+            GRAPHEMES,
+        ])
+        .assert()
+        .success()
+        .stdout("ne\u{301}e\u{301} \u{1F468}\u{200D}\u{1F469} xyz\n");
+
+    Ok(())
+}
+
+#[test]
+fn chars_invalid_utf8_fail() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "30", "--invalid-utf8=fail", GRAPHEMES, ONE])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(format!(
+            "error reading {}: invalid UTF-8 in input\n",
+            GRAPHEMES
+        ))
+        .stdout(format!(
+            "==> {} <==\nne\u{301}e\u{301} \u{1F468}\u{200D}\u{1F469} x\n==> {} <==\nÖne line, four words.\n",
+            GRAPHEMES, ONE
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn chars_stdin_leaves_rest() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "2", "--graphemes"])
+        .write_stdin("ne\u{301}xt")
+        .assert()
+        .success()
+        .stdout("ne\u{301}");
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_chars() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "x", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal character count -- x"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+const TEN_SANDWICH: &str = "one\ntwo\n… (6 lines omitted) …\nnine\nten\n";
+
+#[test]
+fn ten_head_and_tail() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--head", "2", "--tail", "2", TEN])
+        .assert()
+        .success()
+        .stdout(TEN_SANDWICH);
+
+    Ok(())
+}
+
+#[test]
+fn ten_head_and_tail_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--head", "2", "--tail", "2"])
+        .pipe_stdin(TEN)?
+        .assert()
+        .success()
+        .stdout(TEN_SANDWICH);
+
+    Ok(())
+}
+
+#[test]
+fn head_and_tail_overlapping() -> TestResult {
+    let expected = fs::read_to_string(TEN)?;
+    for args in [
+        ["--head", "4", "--tail", "6"],
+        ["--head", "9", "--tail", "9"],
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .arg(TEN)
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn head_and_tail_multiple_files() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--head", "1", "--tail", "1", THREE, EMPTY])
+        .assert()
+        .success()
+        .stdout(format!(
+            "==> {} <==\nThree\r\n… (1 line omitted) …\nfour words.\n\n==> {} <==\n",
+            THREE, EMPTY
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn head_and_tail_large_file() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("big.log");
+    let text: String = (1..=100_000).map(|n| format!("{}\n", n)).collect();
+    fs::write(&path, &text)?;
+    let expected = "1\n2\n… (99995 lines omitted) …\n99998\n99999\n100000\n";
+    Command::cargo_bin(PRG)?
+        .args(["--head", "2", "--tail", "3"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(expected);
+    Command::cargo_bin(PRG)?
+        .args(["--head", "2", "--tail", "3"])
+        .write_stdin(text)
+        .assert()
+        .success()
+        .stdout(expected);
+
+    Ok(())
+}
+
+#[test]
+fn dies_head_without_tail() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--head", "2", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--tail <N>"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn ten_skip_lines() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--skip-lines", "3", "-n", "2", TEN])
+        .assert()
+        .success()
+        .stdout("four\nfive\n");
+
+    Ok(())
+}
+
+#[test]
+fn ten_skip_lines_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--skip-lines", "3", "-n", "2"])
+        .pipe_stdin(TEN)?
+        .assert()
+        .success()
+        .stdout("four\nfive\n");
+
+    Ok(())
+}
+
+#[test]
+fn ten_skip_bytes() -> TestResult {
+    for stdin in [false, true] {
+        let mut cmd = Command::cargo_bin(PRG)?;
+        cmd.args(["--skip-bytes", "4", "-c", "3"]);
+        if stdin {
+            cmd.pipe_stdin(TEN)?;
+        } else {
+            cmd.arg(TEN);
+        }
+        cmd.assert().success().stdout("two");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn skip_past_end() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--skip-bytes", "1K", TEN, ONE])
+        .assert()
+        .success()
+        .stdout(format!("==> {} <==\n\n==> {} <==\n", TEN, ONE));
+
+    Ok(())
+}
+
+#[test]
+fn skip_lines_with_line_index() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("numbers.txt");
+    let index = dir.path().join("numbers.txt.headr-index");
+    let numbers = |last: u32| (0..=last).map(|n| format!("{}\n", n)).collect::<String>();
+    let skip = |n: &str| -> Result<Output, Box<dyn Error>> {
+        Ok(Command::cargo_bin(PRG)?
+            .args(["--line-index", "--skip-lines", n, "-n", "2"])
+            .arg(&path)
+            .output()?)
+    };
+
+    fs::write(&path, numbers(9999))?;
+    assert_eq!(skip("3000")?.stdout, b"3000\n3001\n");
+    assert!(index.exists());
+    assert_eq!(skip("5121")?.stdout, b"5121\n5122\n");
+    assert_eq!(skip("99999")?.stdout, b"");
+
+    // A changed file makes the index stale rather than wrong.
+    fs::write(&path, format!("x\n{}", numbers(2999)))?;
+    assert_eq!(skip("2049")?.stdout, b"2048\n2049\n");
+
+    Ok(())
+}
+
+#[test]
+fn dies_skip_lines_and_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--skip-lines", "2", "--skip-bytes", "2", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
+
+#[test]
+fn dies_line_index_without_skip_lines() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--line-index", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--skip-lines <N>"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn three_hex() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--hex", "-c", "40", THREE])
+        .assert()
+        .success()
+        .stdout(
+            "00000000  54 68 72 65 65 0d 0a 6c 69 6e 65 73 2c 0d 0a 66  |Three..lines,..f|\n\
+             00000010  6f 75 72 20 77 6f 72 64 73 2e 0a                 |our words..|\n\
+             0000001b\n",
+        );
+
+    Ok(())
+}
+
+#[test]
+fn three_hex_layout() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--hex", "--hex-group", "2", "--hex-columns", "10", THREE])
+        .assert()
+        .success()
+        .stdout(
+            "00000000  5468 7265 650d 0a6c 696e  |Three..lin|\n\
+             0000000a  6573 2c0d 0a66 6f75 7220  |es,..four |\n\
+             00000014  776f 7264 732e 0a         |words..|\n\
+             0000001b\n",
+        );
+
+    Ok(())
+}
+
+#[test]
+fn hex_exact_rows() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--hex", "-c", "16", "--hex-columns", "8", TEN])
+        .assert()
+        .success()
+        .stdout(
+            "00000000  6f 6e 65 0a 74 77 6f 0a  |one.two.|\n\
+             00000008  74 68 72 65 65 0a 66 6f  |three.fo|\n\
+             00000010\n",
+        );
+
+    Ok(())
+}
+
+#[test]
+fn hex_offsets_after_skip_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--hex", "--skip-bytes", "16", "-c", "4"])
+        .pipe_stdin(TEN)?
+        .assert()
+        .success()
+        .stdout("00000010  75 72 0a 66                                      |ur.f|\n00000014\n");
+
+    Ok(())
+}
+
+#[test]
+fn hex_offsets_after_skip_lines() -> TestResult {
+    let expected = "00000008  74 68 72 65                                      |thre|\n0000000c\n";
+    Command::cargo_bin(PRG)?
+        .args(["--hex", "--skip-lines", "2", "-c", "4", TEN])
+        .assert()
+        .success()
+        .stdout(expected);
+    Command::cargo_bin(PRG)?
+        .args(["--hex", "--skip-lines", "2", "-c", "4"])
+        .write_stdin(fs::read(TEN)?)
+        .assert()
+        .success()
+        .stdout(expected);
+
+    Ok(())
+}
+
+#[test]
+fn hex_multiple_files() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--hex", "-n", "1", EMPTY, TEN])
+        .assert()
+        .success()
+        .stdout(format!(
+            "==> {} <==\n\n==> {} <==\n\
+             00000000  6f 6e 65 0a                                      |one.|\n\
+             00000004\n",
+            EMPTY, TEN
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_hex_columns() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--hex", "--hex-columns", "0", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal hex size -- 0"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+const BINARY: &str = "./tests/inputs/binary.bin";
+
+/// Runs headr with `args` and its stdout on a terminal, through `script`.
+#[cfg(target_os = "linux")]
+fn run_on_terminal(args: &str) -> Result<Output, Box<dyn Error>> {
+    let headr = assert_cmd::cargo::cargo_bin(PRG);
+    let output = process::Command::new("script")
+        .args([
+            "-qec",
+            &format!("{} {}", headr.display(), args),
+            "/dev/null",
+        ])
+        .stdin(Stdio::null())
+        .output()?;
+    Ok(output)
+}
+
+#[test]
+fn binary_to_pipe_unchanged() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg(BINARY)
+        .assert()
+        .success()
+        .stdout("\u{7f}ELF\u{2}\u{1}\u{1}\0\0\0\0\0\0\0\0\0text\n");
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn binary_to_terminal() -> TestResult {
+    let output = run_on_terminal(&format!("{} {}", BINARY, ONE))?;
+    let shown = String::from_utf8(output.stdout)?;
+    assert!(output.status.success());
+    assert!(shown.contains(&format!(
+        "headr: {}: binary file, use --force or --hex",
+        BINARY
+    )));
+    assert!(!shown.contains("ELF"));
+    assert!(shown.contains("Öne line, four words."));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn binary_to_terminal_forced() -> TestResult {
+    for args in [format!("--force {}", BINARY), format!("--hex {}", BINARY)] {
+        let output = run_on_terminal(&args)?;
+        let shown = String::from_utf8(output.stdout)?;
+        assert!(shown.contains("ELF"), "{:?}", shown);
+        assert!(!shown.contains("binary file"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::io::{BufRead, Cursor};
+
+/// The line-by-line `count` that `wcr::count` replaced, for comparison.
+fn count_by_line(mut file: impl BufRead) -> (usize, usize, usize, usize) {
+    let (mut lines, mut words, mut bytes, mut chars) = (0, 0, 0, 0);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let len = file.read_line(&mut line).unwrap();
+        if len == 0 {
+            break;
+        }
+        bytes += len;
+        lines += 1;
+        chars += line.chars().count();
+        words += line.split_whitespace().count();
+    }
+    (lines, words, bytes, chars)
+}
+
+fn inputs() -> Vec<(&'static str, String)> {
+    let log = "2024-05-01T12:00:00Z INFO request served path=/api/v1/items status=200 ms=12\n";
+    let prose = "Hér er frá sagt, hvé fóru til Atla Gjúka synir, en þeir né vissu.\n";
+    vec![
+        ("ascii-log", log.repeat(50_000)),
+        ("utf8-prose", prose.repeat(50_000)),
+    ]
+}
+
+fn bench_count(c: &mut Criterion) {
+    let mut group = c.benchmark_group("count");
+    for (name, text) in inputs() {
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::new("chunked", name), &text, |b, text| {
+            b.iter(|| wcr::count(Cursor::new(black_box(text.as_bytes()))).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("by-line", name), &text, |b, text| {
+            b.iter(|| count_by_line(Cursor::new(black_box(text.as_bytes()))))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_count);
+criterion_main!(benches);
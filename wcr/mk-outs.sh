@@ -1,5 +1,8 @@
 #!/usr/bin/env bash
 
+# Words and characters are counted as in a UTF-8 locale.
+export LC_ALL=C.UTF-8
+
 ROOT="tests/inputs"
 FILES="$ROOT/empty.txt $ROOT/fox.txt $ROOT/atlamal.txt"
 OUT_DIR="tests/expected"
@@ -19,8 +22,21 @@ for FILE in $FILES; do
     wc -wl  $FILE > ${OUT_DIR}/${BASENAME}.wl.out
     wc -cl  $FILE > ${OUT_DIR}/${BASENAME}.cl.out
     wc -ml  $FILE > ${OUT_DIR}/${BASENAME}.ml.out
+    wc -L   $FILE > ${OUT_DIR}/${BASENAME}.L.out
 done
 
+# Invalid UTF-8, control characters and a character cut off at the end.
+MIXED="$ROOT/mixed.txt"
+wc      $MIXED > ${OUT_DIR}/mixed.txt.out
+wc -m   $MIXED > ${OUT_DIR}/mixed.txt.m.out
+wc -lwm $MIXED > ${OUT_DIR}/mixed.txt.lwm.out
+wc -L   $MIXED > ${OUT_DIR}/mixed.txt.L.out
+
+# Tabs, wide and combining characters for the maximum line length.
+WIDE="$ROOT/wide.txt"
+wc -L      $WIDE > ${OUT_DIR}/wide.txt.L.out
+wc -lwmcL  $WIDE > ${OUT_DIR}/wide.txt.lwmcL.out
+
 wc < "$ROOT/atlamal.txt" > "$OUT_DIR/atlamal.txt.stdin.out"
 
 wc      $FILES > $OUT_DIR/all.out
@@ -34,3 +50,4 @@ wc -wm  $FILES > $OUT_DIR/all.wm.out
 wc -wl  $FILES > $OUT_DIR/all.wl.out
 wc -cl  $FILES > $OUT_DIR/all.cl.out
 wc -ml  $FILES > $OUT_DIR/all.ml.out
+wc -lL  $FILES > $OUT_DIR/all.lL.out
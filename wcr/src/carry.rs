@@ -0,0 +1,82 @@
+//! Counts that are taken a line at a time, from a stream fed in chunks that
+//! can end anywhere in a line.
+
+/// The end of a chunk that could not be counted yet, as it may go on in the
+/// next chunk.
+#[derive(Debug, Default)]
+pub(crate) struct Carry {
+    held: Vec<u8>,
+}
+
+impl Carry {
+    /// Passes the lines of `chunk`, after what was held back before them, to
+    /// `count` with their newlines and `true`. The last line without a newline
+    /// may go on in the next chunk, so it is passed with `false`, and `count`
+    /// returns how much of its start it could count. The rest is held back.
+    pub(crate) fn feed(&mut self, chunk: &[u8], mut count: impl FnMut(&[u8], bool) -> usize) {
+        for piece in chunk.split_inclusive(|&byte| byte == b'\n') {
+            let whole = piece.ends_with(b"\n");
+            if self.held.is_empty() {
+                let counted = count(piece, whole);
+                if !whole {
+                    self.held.extend_from_slice(&piece[counted..]);
+                }
+            } else {
+                self.held.extend_from_slice(piece);
+                let counted = count(&self.held, whole);
+                if whole {
+                    self.held.clear();
+                } else {
+                    self.held.drain(..counted);
+                }
+            }
+        }
+    }
+
+    /// Passes what is held back to `count`, as the last line of the input.
+    pub(crate) fn finish(&mut self, count: impl FnOnce(&[u8])) {
+        count(&std::mem::take(&mut self.held));
+    }
+}
+
+/// The last run of valid UTF-8 in `text`, and its offset. Any bytes after it
+/// are left out, as they may start a character that ends in the next chunk.
+pub(crate) fn last_valid(text: &[u8]) -> (usize, &str) {
+    let mut offset = 0;
+    let mut last = (0, "");
+    for chunk in text.utf8_chunks() {
+        last = (offset, chunk.valid());
+        offset += chunk.valid().len() + chunk.invalid().len();
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{last_valid, Carry};
+
+    #[test]
+    fn test_feed() {
+        let mut carry = Carry::default();
+        let mut seen = Vec::new();
+        // Counts all but the last byte of a line that may go on.
+        let mut count = |text: &[u8], whole: bool| {
+            let counted = if whole { text.len() } else { text.len() - 1 };
+            seen.push(String::from_utf8_lossy(&text[..counted]).to_string());
+            counted
+        };
+        carry.feed(b"ab\ncd", &mut count);
+        carry.feed(b"e", &mut count);
+        carry.feed(b"f\ng", &mut count);
+        carry.finish(|text| seen.push(String::from_utf8_lossy(text).to_string()));
+        assert_eq!(seen, ["ab\n", "c", "d", "ef\n", "", "g"]);
+    }
+
+    #[test]
+    fn test_last_valid() {
+        assert_eq!(last_valid(b"abc"), (0, "abc"));
+        assert_eq!(last_valid(b"a\xffbc\xe6\x97"), (2, "bc"));
+        assert_eq!(last_valid(b"ab\xff"), (0, "ab"));
+        assert_eq!(last_valid(b""), (0, ""));
+    }
+}
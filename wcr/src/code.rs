@@ -0,0 +1,475 @@
+//! `--code`: sort the lines of source files into code, comments and blank
+//! lines, by the comment and string syntax of their language.
+//!
+//! A line with any code on it is code, even if it also has a comment. Comment
+//! markers inside strings are not comments, and block comments can be nested
+//! in the languages that allow it.
+
+use crate::carry::Carry;
+use crate::FileInfo;
+use std::path::Path;
+
+/// The syntax of a programming language, as far as telling comments from
+/// code needs it.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Language {
+    pub(crate) name: &'static str,
+    /// File extensions, or whole file names for files like `Makefile`.
+    names: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    /// Whether block comments can contain other block comments.
+    nested: bool,
+    /// How strings start and end, whether a backslash escapes the end, and
+    /// whether they can go on over lines. Others end with their line, unless
+    /// its newline is escaped.
+    strings: &'static [(&'static str, &'static str, bool, bool)],
+    /// Whether `'` starts a character literal, which can hold a quote.
+    char_literals: bool,
+}
+
+const C_STRINGS: &[(&str, &str, bool, bool)] = &[("\"", "\"", true, false)];
+const C_BLOCK: &[(&str, &str)] = &[("/*", "*/")];
+const SCRIPT_STRINGS: &[(&str, &str, bool, bool)] =
+    &[("\"", "\"", true, false), ("'", "'", true, false)];
+const JS_STRINGS: &[(&str, &str, bool, bool)] = &[
+    ("\"", "\"", true, false),
+    ("'", "'", true, false),
+    ("`", "`", true, true),
+];
+
+/// Languages known to `--code`, looked up by `detect`.
+const LANGUAGES: &[Language] = &[
+    Language {
+        name: "C",
+        names: &["c", "h"],
+        line_comments: &["//"],
+        block_comments: C_BLOCK,
+        nested: false,
+        strings: C_STRINGS,
+        char_literals: true,
+    },
+    Language {
+        name: "C++",
+        names: &["cc", "cpp", "cxx", "hh", "hpp", "hxx"],
+        line_comments: &["//"],
+        block_comments: C_BLOCK,
+        nested: false,
+        strings: C_STRINGS,
+        char_literals: true,
+    },
+    Language {
+        name: "CSS",
+        names: &["css"],
+        line_comments: &[],
+        block_comments: C_BLOCK,
+        nested: false,
+        strings: SCRIPT_STRINGS,
+        char_literals: false,
+    },
+    Language {
+        name: "Go",
+        names: &["go"],
+        line_comments: &["//"],
+        block_comments: C_BLOCK,
+        nested: false,
+        strings: &[("\"", "\"", true, false), ("`", "`", false, true)],
+        char_literals: true,
+    },
+    Language {
+        name: "Haskell",
+        names: &["hs"],
+        line_comments: &["--"],
+        block_comments: &[("{-", "-}")],
+        nested: true,
+        strings: C_STRINGS,
+        char_literals: true,
+    },
+    Language {
+        name: "HTML",
+        names: &["htm", "html", "xml", "svg"],
+        line_comments: &[],
+        block_comments: &[("<!--", "-->")],
+        nested: false,
+        strings: &[],
+        char_literals: false,
+    },
+    Language {
+        name: "Java",
+        names: &["java"],
+        line_comments: &["//"],
+        block_comments: C_BLOCK,
+        nested: false,
+        strings: &[("\"\"\"", "\"\"\"", true, true), ("\"", "\"", true, false)],
+        char_literals: true,
+    },
+    Language {
+        name: "JavaScript",
+        names: &["js", "mjs", "cjs", "jsx"],
+        line_comments: &["//"],
+        block_comments: C_BLOCK,
+        nested: false,
+        strings: JS_STRINGS,
+        char_literals: false,
+    },
+    Language {
+        name: "Lua",
+        names: &["lua"],
+        line_comments: &["--"],
+        block_comments: &[("--[[", "]]")],
+        nested: false,
+        strings: &[
+            ("[[", "]]", false, true),
+            ("\"", "\"", true, false),
+            ("'", "'", true, false),
+        ],
+        char_literals: false,
+    },
+    Language {
+        name: "Makefile",
+        names: &["Makefile", "makefile", "GNUmakefile", "mk"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested: false,
+        strings: &[],
+        char_literals: false,
+    },
+    Language {
+        name: "Python",
+        names: &["py", "pyi"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested: false,
+        strings: &[
+            ("\"\"\"", "\"\"\"", true, true),
+            ("'''", "'''", true, true),
+            ("\"", "\"", true, false),
+            ("'", "'", true, false),
+        ],
+        char_literals: false,
+    },
+    Language {
+        name: "Ruby",
+        names: &["rb"],
+        line_comments: &["#"],
+        block_comments: &[("=begin", "=end")],
+        nested: false,
+        strings: SCRIPT_STRINGS,
+        char_literals: false,
+    },
+    Language {
+        name: "Rust",
+        names: &["rs"],
+        line_comments: &["//"],
+        block_comments: C_BLOCK,
+        nested: true,
+        strings: &[
+            ("r##\"", "\"##", false, true),
+            ("r#\"", "\"#", false, true),
+            ("r\"", "\"", false, true),
+            ("\"", "\"", true, true),
+        ],
+        char_literals: true,
+    },
+    Language {
+        name: "Shell",
+        names: &["sh", "bash", "zsh"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested: false,
+        strings: &[("\"", "\"", true, true), ("'", "'", false, true)],
+        char_literals: false,
+    },
+    Language {
+        name: "SQL",
+        names: &["sql"],
+        line_comments: &["--"],
+        block_comments: C_BLOCK,
+        nested: false,
+        strings: &[("'", "'", false, false)],
+        char_literals: false,
+    },
+    Language {
+        name: "Swift",
+        names: &["swift"],
+        line_comments: &["//"],
+        block_comments: C_BLOCK,
+        nested: true,
+        strings: &[("\"\"\"", "\"\"\"", true, true), ("\"", "\"", true, false)],
+        char_literals: false,
+    },
+    Language {
+        name: "TOML",
+        names: &["toml"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested: false,
+        strings: &[
+            ("\"\"\"", "\"\"\"", true, true),
+            ("'''", "'''", false, true),
+            ("\"", "\"", true, false),
+            ("'", "'", false, false),
+        ],
+        char_literals: false,
+    },
+    Language {
+        name: "TypeScript",
+        names: &["ts", "tsx", "mts", "cts"],
+        line_comments: &["//"],
+        block_comments: C_BLOCK,
+        nested: false,
+        strings: JS_STRINGS,
+        char_literals: false,
+    },
+    Language {
+        name: "YAML",
+        names: &["yaml", "yml"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested: false,
+        strings: SCRIPT_STRINGS,
+        char_literals: false,
+    },
+];
+
+/// The language of `filename`, going by its extension or its whole name.
+pub(crate) fn detect(filename: &str) -> Option<&'static Language> {
+    let path = Path::new(filename);
+    let name = path.file_name()?.to_str()?;
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    LANGUAGES.iter().find(|language| {
+        language
+            .names
+            .iter()
+            .any(|&known| Some(known) == extension || known == name)
+    })
+}
+
+/// Sorts lines fed to it in chunks of any size into code, comments and blank
+/// lines.
+#[derive(Debug)]
+pub(crate) struct Scanner {
+    language: &'static Language,
+    /// How many block comments the current position is in.
+    depth: usize,
+    /// The end of the string the current position is in, whether a
+    /// backslash escapes it, and whether it can go on over lines.
+    string: Option<(&'static str, bool, bool)>,
+    /// The start of a line that continues in the next chunk.
+    carry: Carry,
+}
+
+impl Scanner {
+    pub(crate) fn new(language: &'static Language) -> Scanner {
+        Scanner {
+            language,
+            depth: 0,
+            string: None,
+            carry: Carry::default(),
+        }
+    }
+
+    pub(crate) fn update(&mut self, chunk: &[u8], info: &mut FileInfo) {
+        // Taken out for `classify` to borrow `self` while it feeds lines.
+        let mut carry = std::mem::take(&mut self.carry);
+        carry.feed(chunk, |line, whole| {
+            if whole {
+                self.classify(line, info);
+                line.len()
+            } else {
+                0
+            }
+        });
+        self.carry = carry;
+    }
+
+    /// Classifies a last line without a newline.
+    pub(crate) fn finish(&mut self, info: &mut FileInfo) {
+        let mut carry = std::mem::take(&mut self.carry);
+        carry.finish(|line| {
+            if !line.is_empty() {
+                self.classify(line, info);
+            }
+        });
+    }
+
+    fn classify(&mut self, line: &[u8], info: &mut FileInfo) {
+        let in_string = self.string.is_some();
+        let (code, comment) = self.scan(line);
+        if code || in_string {
+            info.num_code_lines += 1;
+        } else if comment {
+            info.num_comment_lines += 1;
+        } else {
+            info.num_blank_lines += 1;
+        }
+    }
+
+    /// Goes through `line`, and returns whether it has code and whether it
+    /// has comments on it.
+    fn scan(&mut self, line: &[u8]) -> (bool, bool) {
+        let language = self.language;
+        let mut code = false;
+        let mut comment = self.depth > 0;
+        let mut pos = 0;
+        while pos < line.len() {
+            let rest = &line[pos..];
+            if self.depth > 0 {
+                let block = language.block_comments.iter();
+                if let Some((_, end)) = block
+                    .clone()
+                    .find(|(_, end)| rest.starts_with(end.as_bytes()))
+                {
+                    self.depth -= 1;
+                    pos += end.len();
+                } else if let Some((start, _)) = block
+                    .clone()
+                    .find(|(start, _)| language.nested && rest.starts_with(start.as_bytes()))
+                {
+                    self.depth += 1;
+                    pos += start.len();
+                } else {
+                    pos += 1;
+                }
+            } else if let Some((end, escapes, multiline)) = self.string {
+                if escapes && rest[0] == b'\\' {
+                    pos += 2;
+                } else if rest.starts_with(end.as_bytes()) {
+                    self.string = None;
+                    pos += end.len();
+                } else if rest[0] == b'\n' && !multiline {
+                    self.string = None;
+                    pos += 1;
+                } else {
+                    pos += 1;
+                }
+            } else if rest[0].is_ascii_whitespace() {
+                pos += 1;
+            } else if let Some((start, _)) = language
+                .block_comments
+                .iter()
+                .find(|(start, _)| rest.starts_with(start.as_bytes()))
+            {
+                comment = true;
+                self.depth = 1;
+                pos += start.len();
+            } else if language
+                .line_comments
+                .iter()
+                .any(|start| rest.starts_with(start.as_bytes()))
+            {
+                return (code, true);
+            } else if let Some(&(start, end, escapes, multiline)) = language
+                .strings
+                .iter()
+                .find(|(start, _, _, _)| rest.starts_with(start.as_bytes()))
+            {
+                code = true;
+                self.string = Some((end, escapes, multiline));
+                pos += start.len();
+            } else {
+                code = true;
+                pos += match char_literal(rest) {
+                    Some(len) if language.char_literals => len,
+                    _ => 1,
+                };
+            }
+        }
+        (code, comment)
+    }
+}
+
+/// The length of the character literal at the start of `text`, like `'"'`
+/// or `'\''`, if there is one rather than a lone quote like a Rust lifetime.
+fn char_literal(text: &[u8]) -> Option<usize> {
+    let inner = text.strip_prefix(b"'")?;
+    let len = match inner.first()? {
+        b'\\' => 2 + inner.get(2..)?.iter().position(|&byte| byte == b'\'')?,
+        _ => std::str::from_utf8(&inner[..inner.len().min(4)])
+            .or_else(|err| std::str::from_utf8(&inner[..err.valid_up_to()]))
+            .ok()?
+            .chars()
+            .next()?
+            .len_utf8(),
+    };
+    (inner.get(len) == Some(&b'\'')).then_some(len + 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{char_literal, detect, Scanner};
+    use crate::FileInfo;
+
+    /// Code, comment and blank lines of `text` in the language of `filename`.
+    fn classify(filename: &str, text: &str) -> (usize, usize, usize) {
+        let mut scanner = Scanner::new(detect(filename).unwrap());
+        let mut info = FileInfo::default();
+        // Fed in small chunks to cross lines and tokens.
+        for chunk in text.as_bytes().chunks(3) {
+            scanner.update(chunk, &mut info);
+        }
+        scanner.finish(&mut info);
+        (
+            info.num_code_lines,
+            info.num_comment_lines,
+            info.num_blank_lines,
+        )
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("src/lib.rs").map(|l| l.name), Some("Rust"));
+        assert_eq!(detect("Makefile").map(|l| l.name), Some("Makefile"));
+        assert_eq!(detect("a/b.tar.py").map(|l| l.name), Some("Python"));
+        assert_eq!(detect("README"), None);
+        assert_eq!(detect("-"), None);
+    }
+
+    #[test]
+    fn test_rust() {
+        let text = "\
+// A comment
+fn main() { // and code
+
+    /* block /* nested */
+       still a comment */
+    let s = \"// not a comment\";
+    let c = '\"'; /* a quote
+    in a char */ let x = 1;
+    let r = r#\"raw \\\"quoted\\\" /* \"#;
+}";
+        assert_eq!(classify("main.rs", text), (6, 3, 1));
+    }
+
+    #[test]
+    fn test_c_comments_do_not_nest() {
+        let text = "/* a /* b */\nint x; */\n";
+        assert_eq!(classify("x.c", text), (1, 1, 0));
+    }
+
+    #[test]
+    fn test_multiline_strings() {
+        let text = "s = \"\"\"\n# inside\n\n\"\"\"\n# comment\n";
+        assert_eq!(classify("x.py", text), (4, 1, 0));
+    }
+
+    #[test]
+    fn test_strings_end_with_their_line() {
+        let text = "name: Don't panic\n# a comment\n# another\nkey: value\n";
+        assert_eq!(classify("x.yaml", text), (2, 2, 0));
+        let text = "char *s = \"open\n// a comment\n";
+        assert_eq!(classify("x.c", text), (1, 1, 0));
+        // Unless the newline is escaped.
+        let text = "char *s = \"open \\\n// still the string\";\n// a comment\n";
+        assert_eq!(classify("x.c", text), (2, 1, 0));
+    }
+
+    #[test]
+    fn test_char_literal() {
+        assert_eq!(char_literal(b"'\"' x"), Some(3));
+        assert_eq!(char_literal(b"'\\''"), Some(4));
+        assert_eq!(char_literal(b"'\\u{1F600}'"), Some(11));
+        assert_eq!(char_literal("'ä'".as_bytes()), Some(4));
+        assert_eq!(char_literal(b"'a>(x: &'a str)"), None);
+    }
+}
@@ -0,0 +1,418 @@
+//! Counting over raw bytes, so input that is not valid UTF-8 can still be counted.
+//!
+//! Newlines are counted with SIMD where the CPU supports it. Chunks of plain
+//! ASCII, the common case for logs, have their words counted by comparing
+//! neighbouring bytes, which the compiler vectorizes, instead of one
+//! character at a time.
+//!
+//! Line widths for `-L` follow GNU wc too: a tab advances to the next multiple
+//! of 8, wide East Asian characters take two columns, and characters that are
+//! not printable take none.
+
+use crate::code::{Language, Scanner};
+use crate::graphemes::Graphemes;
+use crate::words::{Segmenter, WordMode};
+use crate::FileInfo;
+use unicode_width::UnicodeWidthChar;
+
+/// Accumulates the counts of a stream fed to it in chunks of any size.
+///
+/// Bytes and newlines are counted as they are. Characters are decoded as
+/// UTF-8, and a sequence that is not valid UTF-8 is neither counted as a
+/// character nor starts or ends a word, like GNU wc does in a UTF-8 locale.
+#[derive(Debug)]
+pub(crate) struct Counter {
+    info: FileInfo,
+    /// Whether words and characters are counted, or only lines and bytes.
+    text: bool,
+    /// Whether the widths of lines are measured.
+    line_width: bool,
+    /// The width of the current line so far.
+    line_pos: usize,
+    in_word: bool,
+    /// Whether the first character that starts or ends a word was part of
+    /// one, if there was such a character; see `merge`.
+    starts_in_word: Option<bool>,
+    /// The start of a character that continues in the next chunk.
+    partial: Vec<u8>,
+    /// Sorts lines into code, comments and blank lines for `--code`.
+    code: Option<Scanner>,
+    /// Counts the words of `--word-mode` in place of words between whitespace.
+    words: Option<Segmenter>,
+    /// Counts grapheme clusters and their display width.
+    graphemes: Option<Graphemes>,
+}
+
+impl Default for Counter {
+    fn default() -> Counter {
+        Counter::new(true, true)
+    }
+}
+
+impl Counter {
+    /// A counter that leaves words and characters at 0 unless `text` is set,
+    /// and the maximum line length unless `line_width` is.
+    pub(crate) fn new(text: bool, line_width: bool) -> Counter {
+        Counter {
+            info: FileInfo::default(),
+            text,
+            line_width,
+            line_pos: 0,
+            in_word: false,
+            starts_in_word: None,
+            partial: Vec::new(),
+            code: None,
+            words: None,
+            graphemes: None,
+        }
+    }
+
+    /// Also counts code, comment and blank lines in `language`.
+    pub(crate) fn with_language(mut self, language: &'static Language) -> Counter {
+        self.code = Some(Scanner::new(language));
+        self
+    }
+
+    /// Counts words as `mode` has them.
+    pub(crate) fn with_word_mode(mut self, mode: &WordMode) -> Counter {
+        self.words = Segmenter::new(mode);
+        self
+    }
+
+    /// Also counts grapheme clusters and their display width.
+    pub(crate) fn with_graphemes(mut self) -> Counter {
+        self.graphemes = Some(Graphemes::default());
+        self
+    }
+
+    pub(crate) fn update(&mut self, chunk: &[u8]) {
+        self.info.num_bytes += chunk.len();
+        self.info.num_lines += bytecount::count(chunk, b'\n');
+        if let Some(scanner) = &mut self.code {
+            scanner.update(chunk, &mut self.info);
+        }
+        if let Some(words) = &mut self.words {
+            words.update(chunk);
+        }
+        if let Some(graphemes) = &mut self.graphemes {
+            graphemes.update(chunk, &mut self.info);
+        }
+        if !self.text && !self.line_width {
+            return;
+        }
+        if self.partial.is_empty() && chunk.is_ascii() {
+            self.count_ascii(chunk);
+            return;
+        }
+
+        let mut data = std::mem::take(&mut self.partial);
+        data.extend_from_slice(chunk);
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    self.count_text(text);
+                    break;
+                }
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    // Cannot fail: `valid` is the valid prefix of `rest`.
+                    self.count_text(std::str::from_utf8(valid).unwrap_or_default());
+                    match err.error_len() {
+                        Some(len) => rest = &invalid[len..],
+                        None => {
+                            self.partial.extend_from_slice(invalid);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The counts of everything fed to `update`. A character cut off at the
+    /// end of the input is invalid and not counted.
+    pub(crate) fn finish(mut self) -> FileInfo {
+        self.end_line();
+        if let Some(scanner) = &mut self.code {
+            scanner.finish(&mut self.info);
+        }
+        if let Some(words) = self.words {
+            self.info.num_words = words.finish();
+        }
+        if let Some(graphemes) = &mut self.graphemes {
+            graphemes.finish(&mut self.info);
+        }
+        self.info
+    }
+
+    /// Adds the counts of `next`, which counted the input right after this
+    /// one's, as if a single counter had seen both.
+    ///
+    /// The input has to be split between two characters, and at the start of
+    /// a line if line widths are measured, words of `--word-mode` or grapheme
+    /// clusters counted.
+    /// A word that goes on across the split was counted by both counters and
+    /// is counted once. Code, comment and blank lines cannot be merged, as a
+    /// comment can go on across it.
+    pub(crate) fn merge(&mut self, mut next: Counter) {
+        if let (Some(words), Some(next_words)) = (&mut self.words, next.words.take()) {
+            words.merge(next_words);
+        }
+        if let (Some(graphemes), Some(next_graphemes)) =
+            (&mut self.graphemes, next.graphemes.take())
+        {
+            graphemes.merge(next_graphemes);
+        }
+        self.end_line();
+        self.line_pos = next.line_pos;
+        self.info += &next.info;
+        if self.in_word && next.starts_in_word == Some(true) {
+            self.info.num_words -= 1;
+        }
+        if next.starts_in_word.is_some() {
+            self.in_word = next.in_word;
+        }
+        self.starts_in_word = self.starts_in_word.or(next.starts_in_word);
+    }
+
+    fn count_ascii(&mut self, chunk: &[u8]) {
+        let (first, last) = match (chunk.first(), chunk.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return,
+        };
+        let has_control = chunk.chunks(64).any(|block| {
+            block
+                .iter()
+                .fold(false, |acc, &byte| acc | is_ascii_control(byte))
+        });
+        if has_control {
+            // Control characters leave the word state alone, so neighbours do not tell.
+            let text = std::str::from_utf8(chunk).unwrap_or_default();
+            self.count_text(text);
+            return;
+        }
+        if self.line_width {
+            self.measure_ascii_lines(chunk);
+        }
+        // Every byte is now either a separator or part of a word, and a word
+        // starts wherever a separator is followed by anything else.
+        self.info.num_chars += chunk.len();
+        self.starts_in_word = self.starts_in_word.or(Some(!is_ascii_separator(first)));
+        self.info.num_words += usize::from(!self.in_word && !is_ascii_separator(first));
+        // Summed in blocks small enough for a `u8`, which vectorizes best.
+        let pairs = chunk[..chunk.len() - 1]
+            .chunks(255)
+            .zip(chunk[1..].chunks(255));
+        self.info.num_words += pairs
+            .map(|(before, after)| {
+                let starts = before.iter().zip(after).fold(0u8, |acc, (&a, &b)| {
+                    acc + u8::from(is_ascii_separator(a) & !is_ascii_separator(b))
+                });
+                usize::from(starts)
+            })
+            .sum::<usize>();
+        self.in_word = !is_ascii_separator(last);
+    }
+
+    fn count_text(&mut self, text: &str) {
+        self.info.num_chars += bytecount::num_chars(text.as_bytes());
+        let bytes = text.as_bytes();
+        let mut pos = 0;
+        while let Some(&byte) = bytes.get(pos) {
+            // Only characters outside ASCII need decoding.
+            let (separator, printable, len) = if byte.is_ascii() {
+                if self.line_width {
+                    self.measure_ascii(byte);
+                }
+                (is_ascii_separator(byte), !is_ascii_control(byte), 1)
+            } else {
+                let c = text[pos..].chars().next().unwrap_or_default();
+                let printable = is_printable(c);
+                if self.line_width && printable {
+                    self.line_pos += c.width().unwrap_or(0);
+                }
+                (is_separator(c), printable, c.len_utf8())
+            };
+            if separator {
+                self.starts_in_word.get_or_insert(false);
+                self.in_word = false;
+            } else if printable {
+                self.starts_in_word.get_or_insert(true);
+                if !self.in_word {
+                    self.in_word = true;
+                    self.info.num_words += 1;
+                }
+            }
+            pos += len;
+        }
+    }
+
+    /// Measures lines of ASCII, where most lines are printable characters only
+    /// and as wide as they are long.
+    fn measure_ascii_lines(&mut self, chunk: &[u8]) {
+        for piece in chunk.split_inclusive(|&byte| is_line_end(byte)) {
+            let (line, ended) = match piece.split_last() {
+                Some((&last, line)) if is_line_end(last) => (line, true),
+                _ => (piece, false),
+            };
+            if line.iter().all(|&byte| (b' '..=b'~').contains(&byte)) {
+                self.line_pos += line.len();
+            } else {
+                line.iter().for_each(|&byte| self.measure_ascii(byte));
+            }
+            if ended {
+                self.end_line();
+            }
+        }
+    }
+
+    fn measure_ascii(&mut self, byte: u8) {
+        match byte {
+            _ if is_line_end(byte) => self.end_line(),
+            b'\t' => self.line_pos += 8 - self.line_pos % 8,
+            b' '..=b'~' => self.line_pos += 1,
+            _ => {}
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.info.max_line_length = self.info.max_line_length.max(self.line_pos);
+        self.line_pos = 0;
+    }
+}
+
+// Written without short-circuiting operators so that loops over them vectorize.
+fn is_ascii_separator(byte: u8) -> bool {
+    (byte == b' ') | (byte.wrapping_sub(b'\t') <= b'\r' - b'\t')
+}
+
+/// Characters after which the width of a line starts again from 0.
+fn is_line_end(byte: u8) -> bool {
+    matches!(byte, b'\n' | b'\r' | b'\x0c')
+}
+
+/// Control characters other than the separators.
+fn is_ascii_control(byte: u8) -> bool {
+    ((byte < b' ') & !is_ascii_separator(byte)) | (byte == 0x7f)
+}
+
+/// Whether `c` separates words, including the non-breaking spaces that GNU wc
+/// treats as separators too.
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() && !matches!(c, '\u{85}' | '\u{2028}' | '\u{2029}')
+}
+
+/// Whether `c` can start a word. Other characters, like control characters,
+/// are passed over without starting or ending one.
+fn is_printable(c: char) -> bool {
+    !c.is_control() && !matches!(c, '\u{2028}' | '\u{2029}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Counter;
+    use crate::FileInfo;
+    use rand::{seq::SliceRandom, Rng};
+
+    fn count(chunks: &[&[u8]]) -> FileInfo {
+        count_with(Counter::new(true, false), chunks)
+    }
+
+    fn max_line_length(chunks: &[&[u8]]) -> usize {
+        count_with(Counter::new(false, true), chunks).max_line_length
+    }
+
+    fn count_with(mut counter: Counter, chunks: &[&[u8]]) -> FileInfo {
+        for chunk in chunks {
+            counter.update(chunk);
+        }
+        counter.finish()
+    }
+
+    fn info(num_lines: usize, num_words: usize, num_chars: usize, num_bytes: usize) -> FileInfo {
+        FileInfo {
+            num_lines,
+            num_words,
+            num_bytes,
+            num_chars,
+            ..FileInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        assert_eq!(count(&[b"a\xffb"]), info(0, 1, 2, 3));
+        assert_eq!(count(&[b"a \xff b\n"]), info(1, 2, 5, 6));
+        assert_eq!(count(&[b"\xff"]), info(0, 0, 0, 1));
+        assert_eq!(count(&[b"\xc0\xaf\xed\xa0\x80"]), info(0, 0, 0, 5));
+        assert_eq!(count(&[b"a\xe2\x80 b"]), info(0, 2, 3, 5));
+    }
+
+    #[test]
+    fn test_gnu_word_rules() {
+        assert_eq!(count(&["a\u{a0}b\u{202f}c".as_bytes()]), info(0, 3, 5, 8));
+        assert_eq!(count(&["a\u{3000}b".as_bytes()]), info(0, 2, 3, 5));
+        assert_eq!(count(&[b"a\x01b"]), info(0, 1, 3, 3));
+        assert_eq!(count(&[b"a \x01 b"]), info(0, 2, 5, 5));
+        assert_eq!(count(&["\u{85}\u{2028}".as_bytes()]), info(0, 0, 2, 5));
+        assert_eq!(count(&["a\u{2028}b".as_bytes()]), info(0, 1, 3, 5));
+        assert_eq!(count(&["\u{200b}".as_bytes()]), info(0, 1, 1, 3));
+    }
+
+    #[test]
+    fn test_ascii_matches_chars() {
+        let alphabet = b"ab  \t\n\r\x0b\x0c\x01\x7f.";
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let len = rng.gen_range(0..64);
+            let text: Vec<u8> = (0..len)
+                .map(|_| *alphabet.choose(&mut rng).unwrap())
+                .collect();
+            let mut by_chars = Counter::default();
+            by_chars.info.num_bytes = text.len();
+            by_chars.info.num_lines = text.iter().filter(|&&byte| byte == b'\n').count();
+            by_chars.count_text(std::str::from_utf8(&text).unwrap());
+            let split = rng.gen_range(0..=text.len());
+            assert_eq!(
+                count_with(Counter::default(), &[&text[..split], &text[split..]]),
+                by_chars.finish(),
+                "{:?}",
+                String::from_utf8_lossy(&text)
+            );
+        }
+    }
+
+    #[test]
+    fn test_lines_and_bytes_only() {
+        let mut counter = Counter::new(false, false);
+        counter.update("Öne wörd,\nzwei\n".as_bytes());
+        assert_eq!(counter.finish(), info(2, 0, 0, 17));
+    }
+
+    #[test]
+    fn test_max_line_length() {
+        let width = |text: &str| max_line_length(&[text.as_bytes()]);
+        assert_eq!(width("a\tb"), 9);
+        assert_eq!(width("abcdefgh\t"), 16);
+        assert_eq!(width("abc\rde"), 3);
+        assert_eq!(width("ab\x0cc"), 2);
+        assert_eq!(width("a\x0bb\x01"), 2);
+        assert_eq!(width("日本語\nab"), 6);
+        assert_eq!(width("e\u{301}x\u{200b}"), 2);
+        assert_eq!(width("a\t😀\n"), 10);
+        assert_eq!(max_line_length(&[b"ab\xffcd\n"]), 4);
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let text = "Öne wörd,\nzwei Wörter €\n".as_bytes();
+        let whole = count(&[text]);
+        for split in 0..text.len() {
+            let (first, second) = text.split_at(split);
+            assert_eq!(count(&[first, second]), whole, "split at {}", split);
+            assert_eq!(max_line_length(&[first, second]), 13, "split at {}", split);
+        }
+        assert_eq!(whole, info(2, 5, 24, 29));
+    }
+}
@@ -0,0 +1,258 @@
+//! `--format`: print the counts as GNU wc's aligned table, or as records with
+//! named fields for other programs to read.
+//!
+//! The fields of a record are named like those of a serialized `FileInfo`.
+
+use crate::{format_counts, Config, FileInfo};
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+/// How `run` prints the counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// aligned columns like GNU wc
+    Table,
+    /// one JSON object with a record per file and the total
+    Json,
+    /// comma-separated values with a header line; the total row has an empty name
+    Csv,
+    /// tab-separated values with a header line; the total row has an empty name
+    Tsv,
+}
+
+/// Named counts, in the order they are printed in.
+type Counts = Vec<(&'static str, usize)>;
+
+/// Prints the counts of the files in a run, or of the groups they are summed
+/// up in, and then their total.
+pub(crate) struct Printer<'a> {
+    config: &'a Config,
+    /// The width of the columns of a table.
+    width: usize,
+    /// The records of a JSON object, which is printed by `finish`.
+    files: Vec<Value>,
+    groups: Vec<Value>,
+    total: Option<Value>,
+}
+
+impl<'a> Printer<'a> {
+    /// Starts the output, which for CSV and TSV means printing the header, as
+    /// it does for a table of `--code` counts, whose columns GNU wc does not
+    /// have.
+    pub(crate) fn new(config: &'a Config, width: usize) -> Printer<'a> {
+        let mut printer = Printer {
+            config,
+            width,
+            files: Vec::new(),
+            groups: Vec::new(),
+            total: None,
+        };
+        if let Some(separator) = printer.separator() {
+            let mut header = vec![config.group_kind().unwrap_or("file")];
+            let counts = printer.counts(0, &FileInfo::default());
+            header.extend(counts.iter().map(|(name, _)| name));
+            header.push("error");
+            println!("{}", header.join(separator));
+        } else if config.format == Format::Table && config.code {
+            let counts = printer.counts(0, &FileInfo::default());
+            let names: Vec<&str> = counts.iter().map(|(name, _)| *name).collect();
+            let longest = names.iter().map(|name| name.len()).max().unwrap_or(0);
+            printer.width = printer.width.max(longest);
+            let header: Vec<String> = names
+                .iter()
+                .map(|name| format!("{:>width$}", name, width = printer.width))
+                .collect();
+            let kind = config.group_kind().unwrap_or("file");
+            println!("{} {}", header.join(" "), kind);
+        }
+        printer
+    }
+
+    /// Prints the counts of a file, which has no `name` if it is unnamed stdin.
+    /// A CSV or TSV row calls it `-`, since only the total has an empty name.
+    pub(crate) fn file(&mut self, name: Option<&str>, info: &FileInfo) {
+        let counts = self.config.columns(info);
+        match self.config.format {
+            Format::Table => self.print_table_line(name, &counts),
+            Format::Json => self.files.push(record("file", name.into(), &counts)),
+            Format::Csv | Format::Tsv => self.print_row(name.unwrap_or("-"), Ok(&counts)),
+        }
+    }
+
+    /// Prints the summed up counts of the `files` files in a group.
+    pub(crate) fn group(&mut self, name: &str, files: usize, info: &FileInfo) {
+        let counts = self.counts(files, info);
+        let kind = self.config.group_kind().unwrap_or("group");
+        match self.config.format {
+            Format::Table => self.print_table_line(Some(name), &counts),
+            Format::Json => self.groups.push(record(kind, name.into(), &counts)),
+            Format::Csv | Format::Tsv => self.print_row(name, Ok(&counts)),
+        }
+    }
+
+    /// Reports a file that could not be counted.
+    pub(crate) fn error(&mut self, name: &str, err: &str) {
+        eprintln!("{}: {}", name, err);
+        match self.config.format {
+            Format::Table => {}
+            Format::Json => {
+                let mut record = Map::new();
+                record.insert("file".to_string(), name.into());
+                record.insert("error".to_string(), err.into());
+                self.files.push(record.into());
+            }
+            Format::Csv | Format::Tsv => self.print_row(name, Err(err)),
+        }
+    }
+
+    /// Prints the total of `files` files. Only a table prints its `name`: a
+    /// record of the total has no name, so no file can be taken for it.
+    pub(crate) fn total(&mut self, name: Option<&str>, files: usize, info: &FileInfo) {
+        let counts = self.counts(files, info);
+        match self.config.format {
+            Format::Table => self.print_table_line(name, &counts),
+            Format::Json => {
+                let mut record = Map::new();
+                for (column, num) in counts {
+                    record.insert(column.to_string(), num.into());
+                }
+                self.total = Some(record.into());
+            }
+            Format::Csv | Format::Tsv => self.print_row("", Ok(&counts)),
+        }
+    }
+
+    /// Ends the output, which for JSON means printing all of it.
+    pub(crate) fn finish(self) {
+        if self.config.format == Format::Json {
+            let mut object = Map::new();
+            object.insert("files".to_string(), self.files.into());
+            if let Some(kind) = self.config.group_kind() {
+                object.insert(plural(kind), self.groups.into());
+            }
+            if let Some(total) = self.total {
+                object.insert("total".to_string(), total);
+            }
+            println!("{}", Value::from(object));
+        }
+    }
+
+    /// The selected counts of `info`, after the number of `files` if these
+    /// are summed up in groups.
+    fn counts(&self, files: usize, info: &FileInfo) -> Counts {
+        let mut counts = Vec::new();
+        if self.config.group_kind().is_some() {
+            counts.push(("files", files));
+        }
+        counts.extend(self.config.columns(info));
+        counts
+    }
+
+    fn print_table_line(&self, name: Option<&str>, counts: &Counts) {
+        let counts: Vec<usize> = counts.iter().map(|(_, num)| *num).collect();
+        println!("{}", format_counts(&counts, self.width, name));
+    }
+
+    /// Prints a row with either counts or why a file has none.
+    fn print_row(&self, name: &str, counts: Result<&Counts, &str>) {
+        let separator = self.separator().unwrap_or_default();
+        let escape = |field: &str| match self.config.format {
+            Format::Tsv => escape_tsv(field),
+            _ => escape_csv(field),
+        };
+        let mut row = vec![escape(name)];
+        match counts {
+            Ok(counts) => {
+                row.extend(counts.iter().map(|(_, num)| num.to_string()));
+                row.push(String::new());
+            }
+            Err(err) => {
+                let columns = self.counts(0, &FileInfo::default()).len();
+                row.extend(vec![String::new(); columns]);
+                row.push(escape(err));
+            }
+        }
+        println!("{}", row.join(separator));
+    }
+
+    fn separator(&self) -> Option<&'static str> {
+        match self.config.format {
+            Format::Csv => Some(","),
+            Format::Tsv => Some("\t"),
+            Format::Table | Format::Json => None,
+        }
+    }
+}
+
+/// A JSON record of `counts` with the name of what was counted as `key`.
+fn record(key: &str, name: Value, counts: &Counts) -> Value {
+    let mut record = Map::new();
+    record.insert(key.to_string(), name);
+    for (column, num) in counts {
+        record.insert(column.to_string(), (*num).into());
+    }
+    record.into()
+}
+
+/// The key of the groups of `kind` in a JSON object.
+fn plural(kind: &str) -> String {
+    match kind.strip_suffix('y') {
+        Some(stem) => format!("{}ies", stem),
+        None => format!("{}s", kind),
+    }
+}
+
+/// Quotes a CSV field as RFC 4180 asks for, if it has to be.
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// TSV fields cannot have tabs or line breaks, so these are written as
+/// backslash escapes, like a backslash itself.
+fn escape_tsv(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_csv, escape_tsv};
+    use crate::{Config, FileInfo};
+    use clap::Parser;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape_csv("fox.txt"), "fox.txt");
+        assert_eq!(escape_csv("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv("say \"hi\"\n"), "\"say \"\"hi\"\"\n\"");
+        assert_eq!(escape_tsv("a\tb\\c\n"), "a\\tb\\\\c\\n");
+    }
+
+    #[test]
+    fn test_columns_are_named_like_fields() {
+        let names: Vec<&str> = [
+            vec!["wcr", "-lwmcL", "--graphemes", "--display-width"],
+            vec!["wcr", "--code"],
+        ]
+        .into_iter()
+        .flat_map(|args| Config::parse_from(args).columns(&FileInfo::default()))
+        .map(|(name, _)| name)
+        .collect();
+        let value = serde_json::to_value(FileInfo::default()).unwrap();
+        let fields: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(names.len(), fields.len());
+        assert!(names.iter().all(|name| fields.contains(name)));
+    }
+}
@@ -0,0 +1,120 @@
+//! `--graphemes` and `--display-width`: characters as a reader sees them,
+//! where an emoji with a skin tone or a family of several people is one
+//! extended grapheme cluster, and how many terminal columns they take.
+//!
+//! A cluster never goes on across a line break, so lines are segmented one at
+//! a time, with the break itself, as `\r\n` is a single cluster. A line that
+//! goes on in the next chunk is counted up to its last cluster.
+
+use crate::carry::{last_valid, Carry};
+use crate::FileInfo;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Counts the grapheme clusters of a stream fed to it in chunks, and sums up
+/// their display widths.
+#[derive(Debug, Default)]
+pub(crate) struct Graphemes {
+    /// The start of a line that continues in the next chunk.
+    carry: Carry,
+}
+
+impl Graphemes {
+    pub(crate) fn update(&mut self, chunk: &[u8], info: &mut FileInfo) {
+        self.carry.feed(chunk, |line, whole| {
+            // The last cluster may take in what follows, like a character cut
+            // off after it.
+            let counted = if whole {
+                line.len()
+            } else {
+                let (offset, valid) = last_valid(line);
+                let last = valid.grapheme_indices(true).next_back();
+                offset + last.map_or(0, |(start, _)| start)
+            };
+            count_line(&line[..counted], info);
+            counted
+        });
+    }
+
+    pub(crate) fn finish(&mut self, info: &mut FileInfo) {
+        self.carry.finish(|line| count_line(line, info));
+    }
+
+    /// Takes over from `next`, which counted the input right after this one's,
+    /// split at the start of a line.
+    pub(crate) fn merge(&mut self, next: Graphemes) {
+        self.carry = next.carry;
+    }
+}
+
+/// Bytes that are not valid UTF-8 are neither clusters nor part of one, like
+/// they are not characters.
+fn count_line(line: &[u8], info: &mut FileInfo) {
+    for chunk in line.utf8_chunks() {
+        for cluster in chunk.valid().graphemes(true) {
+            info.num_graphemes += 1;
+            info.display_width += width(cluster);
+        }
+    }
+}
+
+/// The columns `cluster` takes in a terminal. Control characters, line breaks
+/// and tabs among them, take none, as they do for `-L`.
+fn width(cluster: &str) -> usize {
+    match cluster.chars().next() {
+        Some(c) if c.is_control() => 0,
+        _ => cluster.width(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Graphemes;
+    use crate::FileInfo;
+
+    fn count(chunks: &[&[u8]]) -> (usize, usize) {
+        let mut graphemes = Graphemes::default();
+        let mut info = FileInfo::default();
+        for chunk in chunks {
+            graphemes.update(chunk, &mut info);
+        }
+        graphemes.finish(&mut info);
+        (info.num_graphemes, info.display_width)
+    }
+
+    #[test]
+    fn test_graphemes() {
+        let count = |text: &str| count(&[text.as_bytes()]);
+        assert_eq!(count("abc"), (3, 3));
+        assert_eq!(count("e\u{301}"), (1, 1));
+        assert_eq!(count("👍🏽"), (1, 2));
+        assert_eq!(count("👨\u{200d}👩\u{200d}👧"), (1, 2));
+        assert_eq!(count("🇩🇪"), (1, 2));
+        assert_eq!(count("日本語\r\n"), (4, 6));
+        assert_eq!(count("a\tb\u{200b}\n"), (5, 2));
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        assert_eq!(count(&[b"a\xffb"]), (2, 2));
+        assert_eq!(count(&[b"e\xcc", b"\x81x"]), (2, 2));
+    }
+
+    #[test]
+    fn test_byte_at_a_time() {
+        let text = "👨\u{200d}👩\u{200d}👧 e\u{301}\u{301}🇩🇪🇫🇷🇮\r\n日本語".as_bytes();
+        let text = [text, b"\xff e\xcc\x81\xe6\x97"].concat();
+        assert_eq!(count(&text.chunks(1).collect::<Vec<_>>()), count(&[&text]));
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let text = "👨\u{200d}👩\u{200d}👧 und\r\n👍🏽!\n".as_bytes();
+        let whole = count(&[text]);
+        for split in 0..text.len() {
+            let (first, second) = text.split_at(split);
+            assert_eq!(count(&[first, second]), whole, "split at {}", split);
+        }
+        assert_eq!(whole, (9, 9));
+    }
+}
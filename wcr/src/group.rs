@@ -0,0 +1,172 @@
+//! `--group-by`, `--sort` and `--top`: sum up the counts of files by their
+//! extension or directory, and order what is printed by one of the counts.
+
+use crate::{Config, FileInfo, MyResult};
+use std::cmp::Reverse;
+use std::path::{Component, Path};
+
+/// What `--group-by` sums up the counts of files by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    /// The extension of the file name.
+    Ext,
+    /// The directory the file is in.
+    Dir,
+    /// The first N directories of the path to the file.
+    Depth(usize),
+}
+
+impl GroupBy {
+    /// The group of `filename`. Standard input is a group of its own.
+    pub(crate) fn key(&self, filename: &str) -> String {
+        if filename == "-" {
+            return filename.to_string();
+        }
+        let path = Path::new(filename);
+        match self {
+            GroupBy::Ext => path.extension().map_or("(none)".to_string(), |ext| {
+                ext.to_string_lossy().into_owned()
+            }),
+            GroupBy::Dir => dir_key(path, usize::MAX),
+            GroupBy::Depth(depth) => dir_key(path, *depth),
+        }
+    }
+
+    /// What a group is called in the output.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            GroupBy::Ext => "extension",
+            GroupBy::Dir | GroupBy::Depth(_) => "directory",
+        }
+    }
+}
+
+/// The first `depth` directories of `path`, or `.` for files in the working
+/// directory. The root of an absolute path is not counted as one of them.
+fn dir_key(path: &Path, depth: usize) -> String {
+    let mut root = String::new();
+    let mut dirs = Vec::new();
+    for component in path.parent().into_iter().flat_map(Path::components) {
+        match component {
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => {
+                root.push_str(&component.as_os_str().to_string_lossy());
+            }
+            _ if dirs.len() < depth => dirs.push(component.as_os_str().to_string_lossy()),
+            _ => break,
+        }
+    }
+    match (root, dirs.join("/")) {
+        (root, dirs) if root.is_empty() && dirs.is_empty() => ".".to_string(),
+        (root, dirs) => root + &dirs,
+    }
+}
+
+/// Parses `ext`, `dir` or `depth=N`.
+pub(crate) fn parse_group_by(value: &str) -> Result<GroupBy, String> {
+    match value {
+        "ext" => Ok(GroupBy::Ext),
+        "dir" => Ok(GroupBy::Dir),
+        _ => value
+            .strip_prefix("depth=")
+            .and_then(|depth| depth.parse().ok())
+            .filter(|&depth| depth > 0)
+            .map(GroupBy::Depth)
+            .ok_or_else(|| "expected ext, dir or depth=N with N > 0".to_string()),
+    }
+}
+
+/// A line of output: a file, or a group with the number of its files.
+#[derive(Debug)]
+pub(crate) struct Row {
+    /// None for unnamed stdin.
+    pub(crate) name: Option<String>,
+    pub(crate) files: usize,
+    pub(crate) info: FileInfo,
+}
+
+/// Fails unless `--sort` names something that is printed.
+pub(crate) fn check_sort(config: &Config) -> MyResult<()> {
+    match &config.sort {
+        Some(column) if !sort_columns(config).contains(&column.as_str()) => Err(format!(
+            "cannot sort by '{}', expected one of: {}",
+            column,
+            sort_columns(config).join(", ")
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+fn sort_columns(config: &Config) -> Vec<&'static str> {
+    let mut columns = vec!["name"];
+    if config.group_kind().is_some() {
+        columns.push("files");
+    }
+    columns.extend(
+        config
+            .columns(&FileInfo::default())
+            .iter()
+            .map(|(name, _)| *name),
+    );
+    columns
+}
+
+/// Orders `rows` by `--sort`, the largest counts first, and keeps the first
+/// `--top` of them.
+pub(crate) fn sort_rows(config: &Config, rows: &mut Vec<Row>) {
+    match config.sort.as_deref() {
+        None => {}
+        Some("name") => rows.sort_by(|a, b| a.name.cmp(&b.name)),
+        Some("files") => rows.sort_by_key(|row| Reverse(row.files)),
+        Some(column) => rows.sort_by_cached_key(|row| {
+            let counts = config.columns(&row.info);
+            let count = counts.iter().find(|(name, _)| *name == column);
+            Reverse(count.map_or(0, |(_, num)| *num))
+        }),
+    }
+    if let Some(top) = config.top {
+        rows.truncate(usize::try_from(top).unwrap_or(usize::MAX));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_group_by, GroupBy};
+
+    #[test]
+    fn test_parse_group_by() {
+        assert_eq!(parse_group_by("ext"), Ok(GroupBy::Ext));
+        assert_eq!(parse_group_by("dir"), Ok(GroupBy::Dir));
+        assert_eq!(parse_group_by("depth=2"), Ok(GroupBy::Depth(2)));
+        assert!(parse_group_by("depth=0").is_err());
+        assert!(parse_group_by("depth=x").is_err());
+        assert!(parse_group_by("size").is_err());
+    }
+
+    #[test]
+    fn test_key() {
+        assert_eq!(GroupBy::Ext.key("src/lib.rs"), "rs");
+        assert_eq!(GroupBy::Ext.key("Makefile"), "(none)");
+        assert_eq!(GroupBy::Dir.key("src/nested/lib.rs"), "src/nested");
+        assert_eq!(GroupBy::Dir.key("./src/lib.rs"), "src");
+        assert_eq!(GroupBy::Dir.key("fox.txt"), ".");
+        assert_eq!(GroupBy::Dir.key("/tmp/fox.txt"), "/tmp");
+        assert_eq!(GroupBy::Depth(1).key("src/nested/deep/lib.rs"), "src");
+        assert_eq!(
+            GroupBy::Depth(2).key("src/nested/deep/lib.rs"),
+            "src/nested"
+        );
+        assert_eq!(GroupBy::Depth(2).key("src/lib.rs"), "src");
+        assert_eq!(GroupBy::Depth(1).key("-"), "-");
+    }
+
+    #[test]
+    fn test_key_absolute() {
+        assert_eq!(GroupBy::Dir.key("/fox.txt"), "/");
+        assert_eq!(GroupBy::Dir.key("/tmp/g/src/lib.rs"), "/tmp/g/src");
+        assert_eq!(GroupBy::Depth(1).key("/tmp/g/src/lib.rs"), "/tmp");
+        assert_eq!(GroupBy::Depth(2).key("/tmp/g/src/lib.rs"), "/tmp/g");
+        assert_eq!(GroupBy::Depth(1).key("/fox.txt"), "/");
+    }
+}
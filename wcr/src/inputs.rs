@@ -0,0 +1,177 @@
+//! The files `run` counts: the operands or the names listed by
+//! `--files0-from`, with directories among them walked by `-r`.
+
+use crate::{Config, MyResult};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::{DirEntry, WalkBuilder};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// A file to count, or a name that turned out not to be one while looking
+/// for files.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Input {
+    pub(crate) name: String,
+    /// Why there is nothing to count for `name`.
+    pub(crate) error: Option<String>,
+}
+
+impl Input {
+    pub(crate) fn file(name: &str) -> Input {
+        Input {
+            name: name.to_string(),
+            error: None,
+        }
+    }
+
+    fn error(name: &str, error: impl ToString) -> Input {
+        Input {
+            name: name.to_string(),
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// The files named on the command line or in `--files0-from`, in order.
+pub(crate) fn inputs(config: &Config) -> MyResult<Vec<Input>> {
+    let inputs = match &config.files0_from {
+        Some(list) => read_names(list)?,
+        // Like grep, `-r` without operands walks the working directory.
+        None if config.files.is_empty() && config.recursive => vec![Input::file(".")],
+        None => config.files.iter().map(|name| Input::file(name)).collect(),
+    };
+    if !config.recursive {
+        return Ok(inputs);
+    }
+
+    let filter = Filter::new(config)?;
+    let mut walked = Vec::new();
+    for input in inputs {
+        if input.error.is_none() && fs::metadata(&input.name).is_ok_and(|m| m.is_dir()) {
+            walk(&input.name, &filter, &mut walked);
+        } else {
+            walked.push(input);
+        }
+    }
+    Ok(walked)
+}
+
+/// The NUL-terminated names in the file `list`, or stdin if it is `-`, as
+/// GNU wc reads them for `--files0-from`.
+fn read_names(list: &str) -> MyResult<Vec<Input>> {
+    let mut data = Vec::new();
+    let read = match list {
+        "-" => io::stdin().read_to_end(&mut data),
+        _ => fs::File::open(list).and_then(|mut file| file.read_to_end(&mut data)),
+    };
+    read.map_err(|err| format!("cannot open '{}' for reading: {}", list, err))?;
+
+    // The last name does not need to be terminated.
+    if data.last() == Some(&0) {
+        data.pop();
+    }
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+    let names = data.split(|&byte| byte == 0).enumerate();
+    Ok(names.map(|(i, name)| listed(list, i + 1, name)).collect())
+}
+
+/// The `number`th name in `list`, unless it cannot name a file.
+fn listed(list: &str, number: usize, name: &[u8]) -> Input {
+    match std::str::from_utf8(name) {
+        Ok("") => Input::error(
+            &format!("{}:{}", list, number),
+            "invalid zero-length file name",
+        ),
+        Ok("-") if list == "-" => Input::error(
+            "-",
+            "when reading file names from stdin, no file name of '-' allowed",
+        ),
+        Ok(name) => Input::file(name),
+        Err(_) => Input::error(&String::from_utf8_lossy(name), "invalid UTF-8 in file name"),
+    }
+}
+
+/// Which files `-r` counts, going by `--include`, `--exclude` and `--gitignore`.
+#[derive(Clone)]
+struct Filter {
+    include: GlobSet,
+    exclude: GlobSet,
+    gitignore: bool,
+}
+
+impl Filter {
+    fn new(config: &Config) -> MyResult<Filter> {
+        Ok(Filter {
+            include: glob_set(&config.include)?,
+            exclude: glob_set(&config.exclude)?,
+            gitignore: config.gitignore,
+        })
+    }
+
+    /// Whether to look at `entry` at all, as a file or a directory to walk.
+    fn enters(&self, entry: &DirEntry, root: &Path) -> bool {
+        let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+        // Operands themselves are always walked.
+        entry.depth() == 0
+            || !(matches(&self.exclude, entry.path(), root)
+                || self.gitignore && is_dir && entry.file_name() == ".git")
+    }
+
+    /// Whether to count the file `entry`.
+    fn counts(&self, entry: &DirEntry, root: &Path) -> bool {
+        entry.file_type().is_some_and(|t| t.is_file())
+            && (self.include.is_empty() || matches(&self.include, entry.path(), root))
+    }
+}
+
+/// Globs are matched against the name of a file as well as against its path
+/// below the walked directory `root`.
+fn matches(globs: &GlobSet, path: &Path, root: &Path) -> bool {
+    path.file_name().is_some_and(|name| globs.is_match(name))
+        || path
+            .strip_prefix(root)
+            .is_ok_and(|path| globs.is_match(path))
+}
+
+fn glob_set(globs: &[String]) -> MyResult<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|err| format!("invalid glob '{}': {}", glob, err))?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Adds the files below `dir` to `inputs`, sorted by name.
+fn walk(dir: &str, filter: &Filter, inputs: &mut Vec<Input>) {
+    let root = PathBuf::from(dir);
+    let enters = {
+        let (filter, root) = (filter.clone(), root.clone());
+        move |entry: &DirEntry| filter.enters(entry, &root)
+    };
+    let walker = WalkBuilder::new(dir)
+        .standard_filters(false)
+        .git_ignore(filter.gitignore)
+        .git_exclude(filter.gitignore)
+        .git_global(filter.gitignore)
+        .parents(filter.gitignore)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(enters)
+        .build();
+    for entry in walker {
+        match entry {
+            Err(err) => inputs.push(Input::error(dir, err)),
+            Ok(entry) if filter.counts(&entry, &root) => match entry.path().to_str() {
+                Some(name) => inputs.push(Input::file(name)),
+                None => inputs.push(Input::error(
+                    &entry.path().to_string_lossy(),
+                    "invalid UTF-8 in file name",
+                )),
+            },
+            Ok(_) => {}
+        }
+    }
+}
@@ -1,16 +1,59 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, BufRead, BufReader};
+use std::ops::AddAssign;
+
+mod carry;
+mod code;
+mod counter;
+mod format;
+mod graphemes;
+mod group;
+mod inputs;
+mod parallel;
+mod words;
+
+use counter::Counter;
+pub use format::Format;
+use format::Printer;
+pub use group::GroupBy;
+use group::Row;
+use inputs::Input;
+pub use words::WordMode;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
 #[derive(Debug, Parser)]
 #[command(version, author, about)]
 pub struct Config {
-    #[arg(default_value = "-")]
+    /// files to count; standard input if none are given or FILE is -
+    #[arg(value_name = "FILE")]
     files: Vec<String>,
 
+    /// count the files named by NUL-terminated names in file F; standard
+    /// input if F is -
+    #[arg(long, value_name = "F", conflicts_with = "files")]
+    files0_from: Option<String>,
+
+    /// count the files in directories and their subdirectories
+    #[arg(long, short)]
+    recursive: bool,
+
+    /// with -r, count only files whose name or path matches GLOB
+    #[arg(long, value_name = "GLOB", requires = "recursive")]
+    include: Vec<String>,
+
+    /// with -r, skip files and directories whose name or path matches GLOB
+    #[arg(long, value_name = "GLOB", requires = "recursive")]
+    exclude: Vec<String>,
+
+    /// with -r, skip what .gitignore files ignore, and .git directories
+    #[arg(long, requires = "recursive")]
+    gitignore: bool,
+
     /// print the newline counts
     #[arg(long, short)]
     lines: bool,
@@ -26,21 +69,145 @@ pub struct Config {
     /// print the character counts
     #[arg(long, short = 'm')]
     chars: bool,
+
+    /// what -w counts as a word: runs of non-whitespace (whitespace), words
+    /// between Unicode word boundaries (unicode), or matches of a regular
+    /// expression within a line (regex:PATTERN)
+    #[arg(
+        long,
+        value_name = "MODE",
+        default_value = "whitespace",
+        value_parser = words::parse_word_mode
+    )]
+    word_mode: WordMode,
+
+    /// print the extended grapheme cluster counts, characters as a reader
+    /// sees them
+    #[arg(long)]
+    graphemes: bool,
+
+    /// print the maximum display width
+    #[arg(long, short = 'L')]
+    max_line_length: bool,
+
+    /// print the summed display width of all characters
+    #[arg(long)]
+    display_width: bool,
+
+    /// print code, comment and blank lines for each language, under a header;
+    /// files in languages it does not know are reported and left out
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "lines",
+            "words",
+            "bytes",
+            "chars",
+            "graphemes",
+            "max_line_length",
+            "display_width",
+        ]
+    )]
+    code: bool,
+
+    /// sum up the counts of files by their extension (ext), directory (dir)
+    /// or first N directories (depth=N)
+    #[arg(long, value_name = "KEY", value_parser = group::parse_group_by)]
+    group_by: Option<GroupBy>,
+
+    /// print files or groups ordered by COLUMN, largest first, or by name
+    #[arg(long, value_name = "COLUMN")]
+    sort: Option<String>,
+
+    /// print only the first K files or groups
+    #[arg(long, value_name = "K", value_parser = clap::value_parser!(u64).range(1..))]
+    top: Option<u64>,
+
+    /// when to print a line with total counts
+    #[arg(long, value_enum, value_name = "WHEN", default_value = "auto")]
+    total: TotalMode,
+
+    /// count each large regular file in parallel with N threads
+    #[arg(
+        long,
+        short,
+        value_name = "N",
+        default_value = "1",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    jobs: u64,
+
+    /// how to print the counts
+    #[arg(long, value_enum, value_name = "FORMAT", default_value = "table")]
+    format: Format,
 }
 
-#[derive(Debug, PartialEq)]
+/// When `run` prints the `total` line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TotalMode {
+    /// only when more than one file is given
+    Auto,
+    /// always, even for a single file
+    Always,
+    /// only the total, without a line per file or the word "total"
+    Only,
+    /// never
+    Never,
+}
+
+/// The counts of a file, serialized with the names `--format` uses.
+#[derive(Debug, Default, PartialEq, Serialize)]
 pub struct FileInfo {
+    #[serde(rename = "lines")]
     num_lines: usize,
+    #[serde(rename = "words")]
     num_words: usize,
+    #[serde(rename = "bytes")]
     num_bytes: usize,
+    #[serde(rename = "chars")]
     num_chars: usize,
+    #[serde(rename = "graphemes")]
+    num_graphemes: usize,
+    max_line_length: usize,
+    display_width: usize,
+    #[serde(rename = "code")]
+    num_code_lines: usize,
+    #[serde(rename = "comments")]
+    num_comment_lines: usize,
+    #[serde(rename = "blanks")]
+    num_blank_lines: usize,
+}
+
+impl AddAssign<&FileInfo> for FileInfo {
+    fn add_assign(&mut self, other: &FileInfo) {
+        self.num_lines += other.num_lines;
+        self.num_words += other.num_words;
+        self.num_bytes += other.num_bytes;
+        self.num_chars += other.num_chars;
+        self.num_graphemes += other.num_graphemes;
+        // Like GNU wc, the total has the longest line of all files.
+        self.max_line_length = self.max_line_length.max(other.max_line_length);
+        self.display_width += other.display_width;
+        self.num_code_lines += other.num_code_lines;
+        self.num_comment_lines += other.num_comment_lines;
+        self.num_blank_lines += other.num_blank_lines;
+    }
 }
 
 pub fn get_args() -> MyResult<Config> {
     let config = Config::parse();
-    if [config.lines, config.words, config.bytes, config.chars]
-        .iter()
-        .all(|&v| v == false)
+    if [
+        config.lines,
+        config.words,
+        config.bytes,
+        config.chars,
+        config.graphemes,
+        config.max_line_length,
+        config.display_width,
+        config.code,
+    ]
+    .iter()
+    .all(|&v| !v)
     {
         Ok(Config {
             lines: true,
@@ -53,65 +220,312 @@ pub fn get_args() -> MyResult<Config> {
     }
 }
 
+/// Large enough for the vectorized counting to pay off.
+const BUFFER_SIZE: usize = 256 * 1024;
+
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+        "-" => Ok(Box::new(BufReader::with_capacity(BUFFER_SIZE, io::stdin()))),
+        _ => Ok(Box::new(BufReader::with_capacity(
+            BUFFER_SIZE,
+            File::open(filename)?,
+        ))),
     }
 }
 
-pub fn count(mut file: impl BufRead) -> MyResult<FileInfo> {
-    let mut num_lines = 0;
-    let mut num_words = 0;
-    let mut num_bytes = 0;
-    let mut num_chars = 0;
+/// Counts lines, words, characters and bytes of `file`.
+///
+/// Bytes that are not valid UTF-8 are counted as bytes, but not as
+/// characters, and they neither start nor end a word.
+pub fn count(file: impl BufRead) -> MyResult<FileInfo> {
+    count_with(file, Counter::default())
+}
 
-    let mut line = String::new();
+fn count_with(file: impl BufRead, mut counter: Counter) -> MyResult<FileInfo> {
+    feed(file, &mut counter)?;
+    Ok(counter.finish())
+}
+
+/// Passes everything in `file` to `counter`.
+fn feed(mut file: impl BufRead, counter: &mut Counter) -> io::Result<()> {
     loop {
-        let bytes = file.read_line(&mut line)?;
-        if bytes == 0 {
-            break;
+        let buffer = file.fill_buf()?;
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        counter.update(buffer);
+        let len = buffer.len();
+        file.consume(len);
+    }
+}
+
+impl Config {
+    /// The selected counts of `info` with their names, in GNU wc's order:
+    /// lines, words, chars, bytes, maximum line length, with grapheme clusters
+    /// after chars and the display width last. With `--code`, the code,
+    /// comment and blank lines instead.
+    fn columns(&self, info: &FileInfo) -> Vec<(&'static str, usize)> {
+        if self.code {
+            return vec![
+                ("code", info.num_code_lines),
+                ("comments", info.num_comment_lines),
+                ("blanks", info.num_blank_lines),
+            ];
         }
-        num_bytes += bytes;
-        num_lines += 1;
-        num_chars += line.chars().count();
-        num_words += line.split_whitespace().count();
+        [
+            (self.lines, "lines", info.num_lines),
+            (self.words, "words", info.num_words),
+            (self.chars, "chars", info.num_chars),
+            (self.graphemes, "graphemes", info.num_graphemes),
+            (self.bytes, "bytes", info.num_bytes),
+            (
+                self.max_line_length,
+                "max_line_length",
+                info.max_line_length,
+            ),
+            (self.display_width, "display_width", info.display_width),
+        ]
+        .iter()
+        .filter(|(selected, _, _)| *selected)
+        .map(|&(_, name, num)| (name, num))
+        .collect()
+    }
+
+    /// Whether words or characters are needed, or just lines and bytes.
+    fn counts_text(&self) -> bool {
+        self.words || self.chars
     }
 
-    Ok(FileInfo {
-        num_lines,
-        num_words,
-        num_bytes,
-        num_chars,
-    })
+    /// Whether grapheme clusters are segmented, for their count or width.
+    fn counts_graphemes(&self) -> bool {
+        self.graphemes || self.display_width
+    }
+
+    fn counter(&self, filename: &str) -> Counter {
+        let mut counter = Counter::new(self.counts_text(), self.max_line_length);
+        if self.words {
+            counter = counter.with_word_mode(&self.word_mode);
+        }
+        if self.counts_graphemes() {
+            counter = counter.with_graphemes();
+        }
+        match code::detect(filename) {
+            Some(language) if self.code => counter.with_language(language),
+            _ => counter,
+        }
+    }
+
+    /// The group whose counts `run` sums up the counts of `filename` in, if
+    /// it prints groups rather than files. With `--code`, files in languages
+    /// it does not know are in none.
+    fn group(&self, filename: &str) -> Option<String> {
+        let language = code::detect(filename).filter(|_| self.code);
+        if self.code && language.is_none() {
+            return None;
+        }
+        match &self.group_by {
+            Some(group_by) => Some(group_by.key(filename)),
+            None => language.map(|language| language.name.to_string()),
+        }
+    }
+
+    /// What the groups from `group` are called in the output.
+    fn group_kind(&self) -> Option<&'static str> {
+        match &self.group_by {
+            Some(group_by) => Some(group_by.kind()),
+            None => self.code.then_some("language"),
+        }
+    }
+
+    fn num_columns(&self) -> usize {
+        if self.code {
+            return 3;
+        }
+        [
+            self.lines,
+            self.words,
+            self.chars,
+            self.graphemes,
+            self.bytes,
+            self.max_line_length,
+            self.display_width,
+        ]
+        .iter()
+        .filter(|&&selected| selected)
+        .count()
+    }
+}
+
+/// The width of each column, worked out like GNU wc does before counting: wide
+/// enough for the summed size of all regular files, and at least 7 if there is
+/// anything else, like a pipe, whose size is not known up front.
+fn column_width(config: &Config, filenames: &[&str]) -> usize {
+    if filenames.len() == 1 && config.num_columns() == 1 {
+        return 1;
+    }
+    let mut min_width = 1;
+    let mut regular_total = 0;
+    for filename in filenames {
+        match metadata(filename) {
+            Some(metadata) if metadata.is_file() => regular_total += metadata.len(),
+            Some(_) => min_width = 7,
+            None => {}
+        }
+    }
+    regular_total.to_string().len().max(min_width)
+}
+
+fn metadata(filename: &str) -> Option<fs::Metadata> {
+    match filename {
+        // Follows stdin to whatever it was redirected from.
+        "-" if cfg!(unix) => fs::metadata("/dev/stdin").ok(),
+        "-" => None,
+        _ => fs::metadata(filename).ok(),
+    }
+}
+
+fn format_counts(counts: &[usize], width: usize, name: Option<&str>) -> String {
+    let mut line = counts
+        .iter()
+        .map(|num| format!("{:>width$}", num, width = width))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if let Some(name) = name {
+        line.push(' ');
+        line.push_str(name);
+    }
+    line
+}
+
+/// Counts what `config` asks for in `filename`.
+fn count_file(config: &Config, filename: &str) -> MyResult<FileInfo> {
+    if let Some(num_bytes) = size_without_reading(config, filename) {
+        return Ok(FileInfo {
+            num_bytes,
+            ..FileInfo::default()
+        });
+    }
+    if config.jobs > 1 && filename != "-" {
+        if let Some(metadata) = metadata(filename).filter(fs::Metadata::is_file) {
+            return parallel::count(config, filename, metadata.len());
+        }
+    }
+    count_with(open(filename)?, config.counter(filename))
+}
+
+/// With nothing but `-c`, the size of a regular file, which GNU wc also takes
+/// from its metadata instead of reading it. Files that claim to be empty, like
+/// those in /proc, are read anyway.
+fn size_without_reading(config: &Config, filename: &str) -> Option<usize> {
+    if config.lines
+        || config.counts_text()
+        || config.counts_graphemes()
+        || config.max_line_length
+        || config.code
+        || filename == "-"
+    {
+        return None;
+    }
+    // Opened all the same, so that unreadable files are reported as usual.
+    let metadata = File::open(filename).ok()?.metadata().ok()?;
+    let len = usize::try_from(metadata.len()).ok()?;
+    (metadata.is_file() && len > 0).then_some(len)
 }
 
 pub fn run(config: Config) -> MyResult<()> {
-    for filename in &config.files {
-        match open(&filename) {
-            Err(err) => eprintln!("{}: {}", filename, err),
-            Ok(file) => {
-                let file_info = count(file)?;
-                //ToDo:
-                // Printing is kinda weird in GNU wc, see:
-                // https://www.gnu.org/software/coreutils/manual/html_node/wc-invocation.html#wc-invocation
-                println!(
-                    "{:>2}{:>3}{:>3} {}",
-                    //"{:>3}{:>4}{:>4} {}",
-                    file_info.num_lines,
-                    file_info.num_words,
-                    file_info.num_bytes,
-                    filename
-                )
+    // Before the inputs are looked for, which may read stdin or a whole tree.
+    group::check_sort(&config)?;
+    // Without arguments, stdin is read and printed without a name.
+    let named = !config.files.is_empty() || config.files0_from.is_some() || config.recursive;
+    let inputs = if named {
+        inputs::inputs(&config)?
+    } else {
+        vec![Input::file("-")]
+    };
+    let filenames: Vec<&str> = inputs.iter().map(|input| input.name.as_str()).collect();
+    // GNU wc does not look at files named on stdin before counting them.
+    let width = if config.files0_from.as_deref() == Some("-") {
+        1
+    } else {
+        column_width(&config, &filenames)
+    };
+    let mut printer = Printer::new(&config, width);
+    // Files are printed as they are counted, unless they are sorted first.
+    let sorted = config.sort.is_some() || config.top.is_some();
+    let mut rows = Vec::new();
+    let mut groups: BTreeMap<String, (usize, FileInfo)> = BTreeMap::new();
+    let mut total = FileInfo::default();
+    let mut num_files = 0;
+    for Input { name, error } in &inputs {
+        let filename = name.as_str();
+        if let Some(error) = error {
+            printer.error(filename, error);
+            continue;
+        }
+        let group = config.group(filename);
+        if config.group_kind().is_some() && group.is_none() {
+            eprintln!("{}: unknown language, not counted", filename);
+            continue;
+        }
+        match count_file(&config, filename) {
+            Err(err) => printer.error(filename, &err.to_string()),
+            Ok(file_info) => {
+                total += &file_info;
+                num_files += 1;
+                match group {
+                    Some(group) => {
+                        let (files, sum) = groups.entry(group).or_default();
+                        *files += 1;
+                        *sum += &file_info;
+                    }
+                    None if config.total == TotalMode::Only => {}
+                    None if sorted => rows.push(Row {
+                        name: named.then(|| filename.to_string()),
+                        files: 1,
+                        info: file_info,
+                    }),
+                    None => printer.file(named.then_some(filename), &file_info),
+                }
+            }
+        }
+    }
+    let num_groups = groups.len();
+    if config.total != TotalMode::Only {
+        rows.extend(groups.into_iter().map(|(name, (files, info))| Row {
+            name: Some(name),
+            files,
+            info,
+        }));
+        group::sort_rows(&config, &mut rows);
+        for row in &rows {
+            match config.group_kind() {
+                Some(_) => printer.group(
+                    row.name.as_deref().unwrap_or_default(),
+                    row.files,
+                    &row.info,
+                ),
+                None => printer.file(row.name.as_deref(), &row.info),
             }
         }
     }
+
+    let print_total = match config.total {
+        TotalMode::Auto if config.group_kind().is_some() => num_groups > 1,
+        TotalMode::Auto => inputs.len() > 1,
+        TotalMode::Always | TotalMode::Only => true,
+        TotalMode::Never => false,
+    };
+    if print_total {
+        let name = (config.total != TotalMode::Only).then_some("total");
+        printer.total(name, num_files, &total);
+    }
+    printer.finish();
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{count, FileInfo};
+    use super::{count, format_counts, FileInfo};
     use std::io::Cursor;
 
     #[test]
@@ -124,7 +538,23 @@ mod tests {
             num_words: 10,
             num_chars: 48,
             num_bytes: 48,
+            max_line_length: 46,
+            ..FileInfo::default()
         };
         assert_eq!(info.unwrap(), expected);
     }
+
+    #[test]
+    fn test_format_counts() {
+        assert_eq!(
+            format_counts(&[1, 9, 48], 2, Some("fox.txt")),
+            " 1  9 48 fox.txt"
+        );
+        assert_eq!(format_counts(&[5, 225], 3, Some("total")), "  5 225 total");
+        assert_eq!(
+            format_counts(&[4, 29, 177], 7, None),
+            "      4      29     177"
+        );
+        assert_eq!(format_counts(&[0], 1, None), "0");
+    }
 }
@@ -0,0 +1,205 @@
+//! `-j N`: count one large regular file on several threads, each reading its
+//! own byte range.
+//!
+//! Ranges are split between two characters, so every thread decodes the same
+//! characters a single pass would. A word that goes on from one range into the
+//! next is counted by both threads, and once again when their counts are merged.
+//! For `-L`, ranges are split at the start of a line instead, as the width of
+//! a line depends on where it starts, and so they are for `--word-mode` and
+//! `--graphemes`, which segment text a line at a time. A range is not split
+//! where no line starts within `MAX_LINE_SCAN` bytes.
+
+use crate::{feed, Config, Counter, FileInfo, MyResult, WordMode, BUFFER_SIZE};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::{panic, thread};
+
+/// Ranges smaller than this are not worth a thread of their own.
+const MIN_RANGE: u64 = 1024 * 1024;
+/// How far to look for the start of a line to split ranges at.
+const MAX_LINE_SCAN: u64 = 1024 * 1024;
+
+/// Counts the regular file `filename` of `len` bytes with up to `config.jobs`
+/// threads.
+pub(crate) fn count(config: &Config, filename: &str, len: u64) -> MyResult<FileInfo> {
+    // Whether a line is code may depend on everything before it.
+    let jobs = if config.code { 1 } else { config.jobs };
+    count_ranges(config, filename, len, (len / MIN_RANGE).clamp(1, jobs))
+}
+
+/// Counts `filename` split into `ranges` ranges of about the same size.
+fn count_ranges(config: &Config, filename: &str, len: u64, ranges: u64) -> MyResult<FileInfo> {
+    let mut file = File::open(filename)?;
+    let mut bounds = vec![0];
+    for i in 1..ranges {
+        let pos = len * i / ranges;
+        let by_lines = config.max_line_length
+            || config.counts_graphemes()
+            || !matches!(config.word_mode, WordMode::Whitespace);
+        let bound = if by_lines {
+            line_start(&mut file, pos)?
+        } else {
+            Some(char_boundary(&mut file, pos)?)
+        };
+        // Without a line start nearby, the range goes on into the next one.
+        if let Some(bound) = bound {
+            bounds.push(bound.clamp(bounds[bounds.len() - 1], len));
+        }
+    }
+    bounds.push(len);
+
+    let counters = thread::scope(|scope| {
+        let workers: Vec<_> = bounds
+            .windows(2)
+            .map(|range| {
+                let (start, end) = (range[0], range[1]);
+                let counter = config.counter(filename);
+                scope.spawn(move || count_range(filename, start, end, counter))
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|err| panic::resume_unwind(err))
+            })
+            .collect::<io::Result<Vec<Counter>>>()
+    })?;
+    let mut merged = config.counter(filename);
+    for counter in counters {
+        merged.merge(counter);
+    }
+    Ok(merged.finish())
+}
+
+/// The first offset from `pos` on that is not in the middle of a character.
+///
+/// Only continuation bytes can be, and at most three of them belong to the
+/// character before, so any further ones are invalid on their own.
+fn char_boundary(file: &mut File, pos: u64) -> io::Result<u64> {
+    file.seek(SeekFrom::Start(pos))?;
+    let mut next = Vec::with_capacity(3);
+    file.take(3).read_to_end(&mut next)?;
+    let continuations = next
+        .iter()
+        .take_while(|&&byte| (0x80..0xc0).contains(&byte))
+        .count();
+    Ok(pos + continuations as u64)
+}
+
+/// The first offset from `pos` on where a line starts, or the end of the file,
+/// if it is no more than `MAX_LINE_SCAN` bytes on.
+fn line_start(file: &mut File, pos: u64) -> io::Result<Option<u64>> {
+    if pos == 0 {
+        return Ok(Some(0));
+    }
+    // A line starts at `pos` itself if there is a newline right before.
+    let from = pos - 1;
+    file.seek(SeekFrom::Start(from))?;
+    let mut reader = BufReader::with_capacity(BUFFER_SIZE, file.take(MAX_LINE_SCAN + 1));
+    let mut scanned = 0;
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            let at_end = scanned <= MAX_LINE_SCAN;
+            return Ok(at_end.then_some(from + scanned));
+        }
+        if let Some(newline) = buffer.iter().position(|&byte| byte == b'\n') {
+            return Ok(Some(from + scanned + newline as u64 + 1));
+        }
+        let len = buffer.len();
+        reader.consume(len);
+        scanned += len as u64;
+    }
+}
+
+fn count_range(filename: &str, start: u64, end: u64, mut counter: Counter) -> io::Result<Counter> {
+    let mut file = File::open(filename)?;
+    file.seek(SeekFrom::Start(start))?;
+    feed(
+        BufReader::with_capacity(BUFFER_SIZE, file.take(end - start)),
+        &mut counter,
+    )?;
+    Ok(counter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_ranges, line_start, MAX_LINE_SCAN};
+    use crate::{count, Config, FileInfo};
+    use clap::Parser;
+    use rand::{seq::SliceRandom, Rng};
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn test_ranges_match_count() {
+        let pieces: [&[u8]; 13] = [
+            b"a",
+            b"bc",
+            b" ",
+            b"\n",
+            b"\t",
+            b"\r",
+            b"\x01",
+            "ö".as_bytes(),
+            "€".as_bytes(),
+            "😀".as_bytes(),
+            "\u{a0}".as_bytes(),
+            b"\xff",
+            b"\x80\x80",
+        ];
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let mut data = Vec::new();
+            for _ in 0..rng.gen_range(0..80) {
+                data.extend_from_slice(pieces.choose(&mut rng).unwrap());
+            }
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            file.write_all(&data).unwrap();
+            let filename = file.path().to_str().unwrap();
+            let all = count(Cursor::new(&data)).unwrap();
+            let without_width = FileInfo {
+                max_line_length: 0,
+                ..count(Cursor::new(&data)).unwrap()
+            };
+            for (args, expected) in [("-lwmc", &without_width), ("-lwmcL", &all)] {
+                let config = Config::parse_from(["wcr", args]);
+                for ranges in 1..=8 {
+                    let info = count_ranges(&config, filename, data.len() as u64, ranges).unwrap();
+                    assert_eq!(
+                        &info,
+                        expected,
+                        "{} in {} ranges of {:?}",
+                        args,
+                        ranges,
+                        String::from_utf8_lossy(&data)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_start() {
+        let mut data = b"ab\ncd".to_vec();
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&data).unwrap();
+        assert_eq!(line_start(&mut file, 0).unwrap(), Some(0));
+        assert_eq!(line_start(&mut file, 1).unwrap(), Some(3));
+        assert_eq!(line_start(&mut file, 3).unwrap(), Some(3));
+        assert_eq!(line_start(&mut file, 4).unwrap(), Some(5));
+
+        // A line start too far away is not looked for to the end.
+        data.resize(data.len() + MAX_LINE_SCAN as usize + 1, b'x');
+        data.push(b'\n');
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&data).unwrap();
+        assert_eq!(line_start(&mut file, 4).unwrap(), None);
+        let near = data.len() as u64 - MAX_LINE_SCAN;
+        assert_eq!(
+            line_start(&mut file, near).unwrap(),
+            Some(data.len() as u64)
+        );
+    }
+}
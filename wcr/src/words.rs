@@ -0,0 +1,236 @@
+//! `--word-mode`: words other than GNU wc's runs of characters between
+//! whitespace, such as the words of UAX #29, which splits `don't-care,really`
+//! into three and finds words in text without spaces, like Chinese.
+//!
+//! No such word goes on from one line into the next, so each line is segmented
+//! on its own. Long lines are counted as they come in, up to where what follows
+//! cannot change their words; a match of a regular expression is taken to be
+//! final once another one follows it.
+
+use crate::carry::{last_valid, Carry};
+use regex::bytes::{Match, Regex};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What `-w` counts as a word.
+#[derive(Debug, Clone)]
+pub enum WordMode {
+    /// Runs of characters between whitespace, like GNU wc.
+    Whitespace,
+    /// Words between the word boundaries of Unicode Standard Annex #29 that
+    /// have a letter or digit in them.
+    Unicode,
+    /// Matches of a regular expression within a line.
+    Regex(Regex),
+}
+
+/// Parses `whitespace`, `unicode` or `regex:PATTERN`.
+pub(crate) fn parse_word_mode(value: &str) -> Result<WordMode, String> {
+    match value {
+        "whitespace" => Ok(WordMode::Whitespace),
+        "unicode" => Ok(WordMode::Unicode),
+        _ => match value.strip_prefix("regex:") {
+            Some(pattern) => Regex::new(pattern)
+                .map(WordMode::Regex)
+                .map_err(|err| err.to_string()),
+            None => Err("expected whitespace, unicode or regex:PATTERN".to_string()),
+        },
+    }
+}
+
+/// Counts the words of a stream fed to it in chunks, unless they are words
+/// between whitespace, which `Counter` counts on its own.
+#[derive(Debug)]
+pub(crate) struct Segmenter {
+    mode: WordMode,
+    /// The end of a line that continues in the next chunk.
+    carry: Carry,
+    /// Where matches of a regular expression go on in what `carry` holds back,
+    /// after the character before them, which `^` and `\b` look at.
+    resume: usize,
+    num_words: usize,
+}
+
+impl Segmenter {
+    pub(crate) fn new(mode: &WordMode) -> Option<Segmenter> {
+        match mode {
+            WordMode::Whitespace => None,
+            _ => Some(Segmenter {
+                mode: mode.clone(),
+                carry: Carry::default(),
+                resume: 0,
+                num_words: 0,
+            }),
+        }
+    }
+
+    pub(crate) fn update(&mut self, chunk: &[u8]) {
+        self.carry.feed(chunk, |text, whole| {
+            if whole {
+                let line = &text[..text.len() - 1];
+                self.num_words += count_words(&self.mode, line, self.resume);
+                self.resume = 0;
+                return text.len();
+            }
+            match &self.mode {
+                WordMode::Regex(regex) => {
+                    // The last match may still grow, so it is matched again.
+                    let ends: Vec<usize> = matches(regex, text, self.resume)
+                        .iter()
+                        .map(|word| word.end())
+                        .collect();
+                    let [.., end, _] = ends[..] else {
+                        return 0;
+                    };
+                    self.num_words += ends.len() - 1;
+                    let counted = char_start(text, end);
+                    self.resume = end - counted;
+                    counted
+                }
+                _ => {
+                    let counted = unicode_end(text);
+                    self.num_words += count_words(&self.mode, &text[..counted], 0);
+                    counted
+                }
+            }
+        });
+    }
+
+    /// The number of words in everything fed to `update`.
+    pub(crate) fn finish(mut self) -> usize {
+        let (mode, resume) = (&self.mode, self.resume);
+        let mut num_words = self.num_words;
+        self.carry
+            .finish(|line| num_words += count_words(mode, line, resume));
+        num_words
+    }
+
+    /// Adds the words `next` counted in the input right after this one's,
+    /// which has to be split at the start of a line.
+    pub(crate) fn merge(&mut self, next: Segmenter) {
+        self.num_words += next.num_words;
+        self.carry = next.carry;
+        self.resume = next.resume;
+    }
+}
+
+/// Up to where the Unicode words of `text`, the start of a line that goes on,
+/// can be counted: all but the last two pieces between word boundaries, as
+/// whether the last one starts a piece may depend on what follows.
+fn unicode_end(text: &[u8]) -> usize {
+    let (offset, valid) = last_valid(text);
+    let mut bounds = valid.split_word_bound_indices().map(|(start, _)| start);
+    offset + bounds.nth_back(1).unwrap_or(0)
+}
+
+/// Where the character that ends before `end` in `text` starts.
+fn char_start(text: &[u8], end: usize) -> usize {
+    (end.saturating_sub(4)..end)
+        .rev()
+        .find(|&pos| text[pos] & 0xc0 != 0x80)
+        .unwrap_or(end - 1)
+}
+
+/// The number of words in `line` from `start` on. Bytes that are not valid
+/// UTF-8 end a Unicode word, and are left to the pattern of a regular
+/// expression, which looks at what comes before `start` for `^` and `\b`.
+fn count_words(mode: &WordMode, line: &[u8], start: usize) -> usize {
+    match mode {
+        WordMode::Whitespace => 0,
+        WordMode::Unicode => String::from_utf8_lossy(&line[start..])
+            .unicode_words()
+            .count(),
+        WordMode::Regex(regex) => matches(regex, line, start).len(),
+    }
+}
+
+/// The matches of `regex` in `text` from `start` on that are not empty.
+fn matches<'t>(regex: &Regex, text: &'t [u8], start: usize) -> Vec<Match<'t>> {
+    let mut found = Vec::new();
+    let mut pos = start;
+    while let Some(word) = text.get(pos..).and_then(|_| regex.find_at(text, pos)) {
+        if word.is_empty() {
+            pos = word.end() + 1;
+        } else {
+            pos = word.end();
+            found.push(word);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_word_mode, Segmenter, WordMode};
+
+    fn count(mode: &str, chunks: &[&str]) -> usize {
+        let mut segmenter = Segmenter::new(&parse_word_mode(mode).unwrap()).unwrap();
+        for chunk in chunks {
+            segmenter.update(chunk.as_bytes());
+        }
+        segmenter.finish()
+    }
+
+    #[test]
+    fn test_parse_word_mode() {
+        assert!(matches!(
+            parse_word_mode("whitespace"),
+            Ok(WordMode::Whitespace)
+        ));
+        assert!(matches!(parse_word_mode("unicode"), Ok(WordMode::Unicode)));
+        assert!(matches!(
+            parse_word_mode("regex:\\w+"),
+            Ok(WordMode::Regex(_))
+        ));
+        assert!(parse_word_mode("regex:(").is_err());
+        assert!(parse_word_mode("letters").is_err());
+    }
+
+    #[test]
+    fn test_unicode() {
+        assert_eq!(count("unicode", &["don't-care,really\n"]), 3);
+        assert_eq!(count("unicode", &["The quick (\"brown\") fox.\n"]), 4);
+        assert_eq!(count("unicode", &["今日は晴れ"]), 5);
+        assert_eq!(count("unicode", &["3.14 ist π\n", "\n"]), 3);
+    }
+
+    #[test]
+    fn test_regex() {
+        assert_eq!(count("regex:[[:alpha:]]+", &["don't-care,really\n"]), 4);
+        assert_eq!(count("regex:\\w*", &["a  b\n"]), 2);
+        assert_eq!(count("regex:a\\nb", &["a\nb\n"]), 0);
+    }
+
+    #[test]
+    fn test_lines_fed_a_byte_at_a_time() {
+        let text = "don't-care,really 日本語 3.14 ist π\u{301}!\n🇩🇪🇫🇷 ^ab cd_e\u{301}\n".as_bytes();
+        let text = [text, b"ab\xffcd \xe6\x97 x"].concat();
+        for mode in [
+            "unicode",
+            "regex:\\w+",
+            "regex:^\\w+",
+            "regex:\\b\\w",
+            "regex:\\w*",
+        ] {
+            let mode = parse_word_mode(mode).unwrap();
+            let count = |chunks: Vec<&[u8]>| {
+                let mut segmenter = Segmenter::new(&mode).unwrap();
+                for chunk in chunks {
+                    segmenter.update(chunk);
+                }
+                segmenter.finish()
+            };
+            let whole = count(vec![&text]);
+            assert_eq!(count(text.chunks(1).collect()), whole, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let text = "don't-care,\nreally 日本語\n";
+        let whole = count("unicode", &[text]);
+        for split in (0..text.len()).filter(|&split| text.is_char_boundary(split)) {
+            let (first, second) = text.split_at(split);
+            assert_eq!(count("unicode", &[first, second]), whole);
+        }
+    }
+}
@@ -1,7 +1,8 @@
-use assert_cmd::Command;
+use assert_cmd::{cargo::CommandCargoExt, Command};
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::fs;
+use std::fs::{self, File};
+use std::process;
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -9,6 +10,8 @@ const PRG: &str = "wcr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const ATLAMAL: &str = "tests/inputs/atlamal.txt";
+const MIXED: &str = "tests/inputs/mixed.txt";
+const WIDE: &str = "tests/inputs/wide.txt";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -162,12 +165,24 @@ fn atlamal_bytes_lines() -> TestResult {
 // --------------------------------------------------
 #[test]
 fn atlamal_stdin() -> TestResult {
-    let input = fs::read_to_string(ATLAMAL)?;
+    // Redirected from the file, as in mk-outs.sh, so the size is known up front.
     let expected = fs::read_to_string("tests/expected/atlamal.txt.stdin.out")?;
+    let output = process::Command::cargo_bin(PRG)?
+        .stdin(File::open(ATLAMAL)?)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_stdin_pipe() -> TestResult {
     Command::cargo_bin(PRG)?
-        .write_stdin(input)
+        .pipe_stdin(ATLAMAL)?
         .assert()
-        .stdout(expected);
+        .success()
+        .stdout("      4      29     177\n");
     Ok(())
 }
 
@@ -212,3 +227,814 @@ fn test_all_words_lines() -> TestResult {
 fn test_all_bytes_lines() -> TestResult {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn atlamal_chars() -> TestResult {
+    run(&["-m", ATLAMAL], "tests/expected/atlamal.txt.m.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_lines_words_chars() -> TestResult {
+    run(&["-lwm", ATLAMAL], "tests/expected/atlamal.txt.lwm.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_words_chars() -> TestResult {
+    run(&["-wm", ATLAMAL], "tests/expected/atlamal.txt.wm.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_chars_lines() -> TestResult {
+    run(&["-ml", ATLAMAL], "tests/expected/atlamal.txt.ml.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_chars() -> TestResult {
+    run(&["-m", EMPTY, FOX, ATLAMAL], "tests/expected/all.m.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_lines_words_chars() -> TestResult {
+    run(&["-lwm", EMPTY, FOX, ATLAMAL], "tests/expected/all.lwm.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_words_chars() -> TestResult {
+    run(&["-wm", EMPTY, FOX, ATLAMAL], "tests/expected/all.wm.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_chars_lines() -> TestResult {
+    run(&["-ml", EMPTY, FOX, ATLAMAL], "tests/expected/all.ml.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn width_ignores_bad_file() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-l", &gen_bad_file(), FOX])
+        .assert()
+        .success()
+        .stdout(format!(" 1 {}\n 1 total\n", FOX));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn total_always() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--total=always", FOX])
+        .assert()
+        .success()
+        .stdout(format!(" 1  9 48 {}\n 1  9 48 total\n", FOX));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn total_only() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--total=only", EMPTY, FOX, ATLAMAL])
+        .assert()
+        .success()
+        .stdout("  5  38 225\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn total_never() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+    let expected = expected.trim_end_matches("  5  38 225 total\n");
+    Command::cargo_bin(PRG)?
+        .args(["--total=never", EMPTY, FOX, ATLAMAL])
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed() -> TestResult {
+    run(&[MIXED], "tests/expected/mixed.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_chars() -> TestResult {
+    run(&["-m", MIXED], "tests/expected/mixed.txt.m.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_lines_words_chars() -> TestResult {
+    run(&["-lwm", MIXED], "tests/expected/mixed.txt.lwm.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn empty_max_line_length() -> TestResult {
+    run(&["-L", EMPTY], "tests/expected/empty.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_max_line_length() -> TestResult {
+    run(&["-L", FOX], "tests/expected/fox.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_max_line_length() -> TestResult {
+    run(
+        &["--max-line-length", ATLAMAL],
+        "tests/expected/atlamal.txt.L.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_max_line_length() -> TestResult {
+    run(&["-L", MIXED], "tests/expected/mixed.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn wide_max_line_length() -> TestResult {
+    run(&["-L", WIDE], "tests/expected/wide.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn wide_all_counts() -> TestResult {
+    run(&["-lwmcL", WIDE], "tests/expected/wide.txt.lwmcL.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_lines_max_line_length() -> TestResult {
+    run(&["-lL", EMPTY, FOX, ATLAMAL], "tests/expected/all.lL.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_unreadable_file() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", FOX])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("tests/inputs: "))
+        .stdout(predicate::str::contains(format!(
+            "      1       9      48 {}\n",
+            FOX
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bytes_of_large_file_from_metadata() -> TestResult {
+    // Sparse, so only reading it would take long.
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("large.bin");
+    File::create(&path)?.set_len(1 << 40)?;
+    Command::cargo_bin(PRG)?
+        .arg("-c")
+        .arg(&path)
+        .timeout(std::time::Duration::from_secs(10))
+        .assert()
+        .success()
+        .stdout(format!("{} {}\n", 1u64 << 40, path.display()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(target_os = "linux")]
+fn bytes_of_proc_file_are_read() -> TestResult {
+    let expected = fs::read("/proc/version")?.len();
+    let output = Command::cargo_bin(PRG)?
+        .args(["-c", "/proc/version"])
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        format!("{} /proc/version\n", expected)
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_match_single_thread() -> TestResult {
+    // Large enough to be split into several ranges.
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("large.txt");
+    let text = fs::read(WIDE)?;
+    fs::write(&path, text.repeat(4 * 1024 * 1024 / text.len() + 1))?;
+    for args in [
+        &["-lwmc"][..],
+        &["-lwmcL"],
+        &["-lw", "--word-mode", "unicode"],
+        &["-m", "--graphemes", "--display-width"],
+    ] {
+        let expected = Command::cargo_bin(PRG)?.args(args).arg(&path).output()?;
+        assert!(expected.status.success());
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .args(["-j", "4"])
+            .arg(&path)
+            .assert()
+            .success()
+            .stdout(expected.stdout);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_zero_jobs() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-j", "0", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value '0'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn format_json() -> TestResult {
+    let bad = gen_bad_file();
+    let output = Command::cargo_bin(PRG)?
+        .args(["--format", "json", "-lwL", FOX, &bad, ATLAMAL])
+        .output()?;
+    assert!(output.status.success());
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let error = "No such file or directory (os error 2)";
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "files": [
+                {"file": FOX, "lines": 1, "words": 9, "max_line_length": 50},
+                {"file": bad, "error": error},
+                {"file": ATLAMAL, "lines": 4, "words": 29, "max_line_length": 43},
+            ],
+            "total": {"lines": 5, "words": 38, "max_line_length": 50},
+        })
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn format_json_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--format", "json"])
+        .pipe_stdin(FOX)?
+        .assert()
+        .success()
+        .stdout("{\"files\":[{\"file\":null,\"lines\":1,\"words\":9,\"bytes\":48}]}\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn format_csv() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!(
+        "file,lines,chars,error\n\
+         {},1,48,\n\
+         {},,,No such file or directory (os error 2)\n\
+         ,1,48,\n",
+        FOX, bad
+    );
+    Command::cargo_bin(PRG)?
+        .args(["--format", "csv", "-lm", FOX, &bad])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn format_csv_quotes_names() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("a,\"b\".txt");
+    fs::copy(FOX, &path)?;
+    let name = path.display().to_string();
+    Command::cargo_bin(PRG)?
+        .args(["--format", "csv", "-c", &name])
+        .assert()
+        .success()
+        .stdout(format!(
+            "file,bytes,error\n\"{}\",48,\n",
+            name.replace('"', "\"\"")
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn format_csv_file_named_total() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    fs::copy(FOX, dir.path().join("total"))?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["--format", "csv", "--total", "always", "-l", "total"])
+        .assert()
+        .success()
+        .stdout("file,lines,error\ntotal,1,\n,1,\n");
+    // Unnamed stdin is `-`, so it is not taken for the total either.
+    Command::cargo_bin(PRG)?
+        .args(["--format", "csv", "--total", "always"])
+        .write_stdin("a b\n")
+        .assert()
+        .success()
+        .stdout("file,lines,words,bytes,error\n-,1,2,4,\n,1,2,4,\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn format_tsv() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--format", "tsv", "--total", "always", ATLAMAL])
+        .assert()
+        .success()
+        .stdout(format!(
+            "file\tlines\twords\tbytes\terror\n{}\t4\t29\t177\t\n\t4\t29\t177\t\n",
+            ATLAMAL
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_file() -> TestResult {
+    let dir = tempfile::tempdir()?;
+    let list = dir.path().join("list");
+    fs::write(&list, format!("{}\0{}\0{}\0", EMPTY, FOX, ATLAMAL))?;
+    run(
+        &["--files0-from", list.to_str().unwrap_or_default()],
+        "tests/expected/all.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("--files0-from=-")
+        .write_stdin(format!("{}\0\0-\0", FOX))
+        .assert()
+        .success()
+        .stdout(format!("1 9 48 {}\n1 9 48 total\n", FOX))
+        .stderr(
+            "-:2: invalid zero-length file name\n\
+             -: when reading file names from stdin, no file name of '-' allowed\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_empty_list() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("--files0-from=-")
+        .write_stdin("")
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_files0_from_with_operands() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from=-", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_files0_from_missing_list() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with(format!(
+            "cannot open '{}' for reading: ",
+            bad
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+fn make_tree() -> Result<tempfile::TempDir, Box<dyn std::error::Error>> {
+    let dir = tempfile::tempdir()?;
+    let root = dir.path();
+    for sub in ["src/nested", "target", ".git"] {
+        fs::create_dir_all(root.join(sub))?;
+    }
+    for (name, text) in [
+        (".gitignore", "target/\n*.log\n"),
+        (".git/HEAD", "ref: refs/heads/main\n"),
+        ("README.md", "# tree\n"),
+        ("src/main.rs", "fn main() {}\n"),
+        ("src/nested/lib.rs", "pub mod a;\npub mod b;\n"),
+        ("src/debug.log", "one two three\n"),
+        ("target/out.rs", "fn generated() {}\n"),
+    ] {
+        fs::write(root.join(name), text)?;
+    }
+    Ok(dir)
+}
+
+// --------------------------------------------------
+fn run_in(dir: &std::path::Path, args: &[&str], expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .current_dir(dir)
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive() -> TestResult {
+    let tree = make_tree()?;
+    run_in(
+        tree.path(),
+        &["-r", "-l", "src", "README.md"],
+        " 1 src/debug.log\n 1 src/main.rs\n 2 src/nested/lib.rs\n 1 README.md\n 5 total\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_include_exclude() -> TestResult {
+    let tree = make_tree()?;
+    run_in(
+        tree.path(),
+        &[
+            "-rl",
+            "--include",
+            "*.rs",
+            "--exclude",
+            "target",
+            "--exclude",
+            "src/main.rs",
+        ],
+        "2 ./src/nested/lib.rs\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_gitignore() -> TestResult {
+    let tree = make_tree()?;
+    run_in(
+        tree.path(),
+        &["-rl", "--gitignore"],
+        " 2 ./.gitignore\n 1 ./README.md\n 1 ./src/main.rs\n 2 ./src/nested/lib.rs\n 6 total\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_include_without_recursive() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--include", "*.rs", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--recursive"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn code() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--code", "-r", "tests/inputs/code"])
+        .assert()
+        .success()
+        .stdout(
+            "   files     code comments   blanks language\n       \
+                    1        5        1        1 Python\n       \
+                    1        5        3        2 Rust\n       \
+                    2       10        4        3 total\n",
+        )
+        .stderr("tests/inputs/code/notes.txt: unknown language, not counted\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn code_single_language() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            "--code",
+            "tests/inputs/code/hello.rs",
+            "tests/inputs/code/notes.txt",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "   files     code comments   blanks language\n       \
+                    1        5        3        2 Rust\n",
+        )
+        .stderr("tests/inputs/code/notes.txt: unknown language, not counted\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn code_unknown_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("--code")
+        .write_stdin("fn main() {}\n")
+        .assert()
+        .success()
+        .stdout("   files     code comments   blanks language\n")
+        .stderr("-: unknown language, not counted\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn code_csv() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--code", "--format", "csv", "-r", "tests/inputs/code"])
+        .assert()
+        .success()
+        .stdout(
+            "language,files,code,comments,blanks,error\n\
+             Python,1,5,1,1,\n\
+             Rust,1,5,3,2,\n\
+             ,2,10,4,3,\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn code_json() -> TestResult {
+    let output = Command::cargo_bin(PRG)?
+        .args(["--code", "--format", "json", "tests/inputs/code/hello.py"])
+        .output()?;
+    assert!(output.status.success());
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "files": [],
+            "languages": [
+                {"language": "Python", "files": 1, "code": 5, "comments": 1, "blanks": 1},
+            ],
+        })
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_code_with_counts() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--code", "-l", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn group_by_ext() -> TestResult {
+    let tree = make_tree()?;
+    run_in(
+        tree.path(),
+        &["-rl", "--gitignore", "--group-by", "ext"],
+        " 1  2 (none)\n 1  1 md\n 2  3 rs\n 4  6 total\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn group_by_depth_sort_top() -> TestResult {
+    let tree = make_tree()?;
+    run_in(
+        tree.path(),
+        &[
+            "-rl",
+            "--gitignore",
+            "--group-by",
+            "depth=1",
+            "--sort",
+            "lines",
+            "--top",
+            "1",
+        ],
+        " 2  3 .\n 4  6 total\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn group_by_dir_csv() -> TestResult {
+    let tree = make_tree()?;
+    run_in(
+        tree.path(),
+        &["-rl", "--gitignore", "--group-by", "dir", "--format", "csv"],
+        "directory,files,lines,error\n.,2,3,\nsrc,1,1,\nsrc/nested,1,2,\n,4,6,\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn group_by_json() -> TestResult {
+    let tree = make_tree()?;
+    let output = Command::cargo_bin(PRG)?
+        .current_dir(tree.path())
+        .args([
+            "-rl",
+            "--gitignore",
+            "--group-by",
+            "depth=1",
+            "--format",
+            "json",
+        ])
+        .output()?;
+    assert!(output.status.success());
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "files": [],
+            "directories": [
+                {"directory": ".", "files": 2, "lines": 3},
+                {"directory": "src", "files": 2, "lines": 3},
+            ],
+            "total": {"files": 4, "lines": 6},
+        })
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_files_top() -> TestResult {
+    let tree = make_tree()?;
+    run_in(
+        tree.path(),
+        &["-rl", "--gitignore", "--sort", "lines", "--top", "2"],
+        " 2 ./.gitignore\n 2 ./src/nested/lib.rs\n 6 total\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_sort_unknown_column() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--sort", "files", FOX])
+        .assert()
+        .failure()
+        .stderr("cannot sort by 'files', expected one of: name, lines\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_sort_unknown_column_before_reading_inputs() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--sort", "bogus", "--files0-from", "does-not-exist"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("cannot sort by 'bogus', expected one of: name, lines, words, bytes\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_group_by() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--group-by", "depth=0", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected ext, dir or depth=N"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn word_mode_unicode() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-w", "--word-mode", "unicode"])
+        .write_stdin("don't-care,really\n今日は晴れ\n")
+        .assert()
+        .success()
+        .stdout("8\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn word_mode_regex() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-lw", "--word-mode", "regex:[[:alpha:]]+", FOX])
+        .assert()
+        .success()
+        .stdout(" 1  9 tests/inputs/fox.txt\n");
+    Command::cargo_bin(PRG)?
+        .args(["-w", "--word-mode", "regex:[[:alpha:]]+"])
+        .write_stdin("don't-care,really\n")
+        .assert()
+        .success()
+        .stdout("4\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_word_mode() -> TestResult {
+    for (mode, expected) in [
+        ("letters", "expected whitespace, unicode or regex:PATTERN"),
+        ("regex:(", "unclosed group"),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(["--word-mode", mode, FOX])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(expected));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn graphemes_and_display_width() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-mc", "--graphemes", "--display-width"])
+        .write_stdin("👨\u{200d}👩\u{200d}👧 👍🏽\n")
+        .assert()
+        .success()
+        .stdout("      9       4      28       5\n");
+    Command::cargo_bin(PRG)?
+        .args(["-m", "--graphemes", "-L", "--display-width", WIDE, FOX])
+        .assert()
+        .success()
+        .stdout(format!(
+            " 64  62  26  60 {}\n 48  48  50  46 {}\n112 110  50 106 total\n",
+            WIDE, FOX
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn graphemes_json() -> TestResult {
+    let output = Command::cargo_bin(PRG)?
+        .args(["--graphemes", "--display-width", "--format", "json", FOX])
+        .output()?;
+    assert!(output.status.success());
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "files": [{"file": FOX, "graphemes": 48, "display_width": 46}],
+        })
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn group_by_absolute_paths() -> TestResult {
+    let tree = make_tree()?;
+    let root = tree.path().to_str().ok_or("temporary path is not UTF-8")?;
+    let top = tree.path().iter().nth(1).ok_or("temporary path is /")?;
+    Command::cargo_bin(PRG)?
+        .args(["-l", "--group-by", "depth=1", "-r", "--gitignore", root])
+        .assert()
+        .success()
+        .stdout(format!(" 4  6 /{}\n", top.to_string_lossy()));
+    Ok(())
+}
@@ -0,0 +1,7 @@
+#!/usr/bin/env python3
+"""A docstring
+# that is a string.
+"""
+
+def main():
+    print("# not a comment")  # a comment
@@ -0,0 +1,10 @@
+//! A greeting.
+
+/* Block comments /* nest */
+   in Rust. */
+fn main() {
+    let url = "https://example.com/*"; // not a comment start
+    let quote = '"';
+
+    println!("{} {}", url, quote);
+}